@@ -0,0 +1,484 @@
+// Copyright (C) 2022 JET PROTOCOL HOLDINGS, LLC.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+use crate::config::AccountOverride;
+use crate::request::RpcRequest;
+
+/// Methods whose upstream responses have the account overrides applied.
+pub const OVERRIDDEN_METHODS: [&str; 5] = [
+    "getAccountInfo",
+    "getBalance",
+    "getMultipleAccounts",
+    "getProgramAccounts",
+    "getTokenAccountBalance",
+];
+
+/// JSON-RPC error code of an invalid request parameter.
+const INVALID_PARAMS: i64 = -32602;
+
+/// Owner of accounts that are created by an override without one.
+const SYSTEM_PROGRAM: &str = "11111111111111111111111111111111";
+
+/// Owners of the accounts that `getTokenAccountBalance` accepts.
+const TOKEN_PROGRAMS: [&str; 2] = [
+    "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
+    "TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb",
+];
+
+/// Applies the argued account overrides to the upstream response of the request,
+/// so that each overridden account is reported consistently by every read method.
+pub fn apply(res: &mut Value, req: &RpcRequest, overrides: &BTreeMap<String, AccountOverride>) {
+    if overrides.is_empty() || res.get("result").is_none() {
+        return;
+    }
+
+    let first = req.params().first();
+    let encoding = req
+        .params()
+        .iter()
+        .find_map(|p| p.get("encoding"))
+        .and_then(Value::as_str);
+
+    match req.method.as_str() {
+        "getAccountInfo" => {
+            let o = match first.and_then(Value::as_str).and_then(|k| overrides.get(k)) {
+                Some(o) => o,
+                None => return,
+            };
+            let value = &mut res["result"]["value"];
+            *value = account(value, o, encoding).unwrap_or(Value::Null);
+        }
+        "getMultipleAccounts" => {
+            let keys = first.and_then(Value::as_array).cloned().unwrap_or_default();
+            if let Some(Value::Array(values)) = res.pointer_mut("/result/value") {
+                for (key, value) in keys.iter().zip(values.iter_mut()) {
+                    if let Some(o) = key.as_str().and_then(|k| overrides.get(k)) {
+                        *value = account(value, o, encoding).unwrap_or(Value::Null);
+                    }
+                }
+            }
+        }
+        "getBalance" => {
+            let o = match first.and_then(Value::as_str).and_then(|k| overrides.get(k)) {
+                Some(o) => o,
+                None => return,
+            };
+            let value = &mut res["result"]["value"];
+            if o.missing {
+                *value = json!(0);
+            } else if let Some(lamports) = o.lamports {
+                *value = json!(lamports);
+            } else if value.is_null() {
+                *value = json!(0);
+            }
+        }
+        "getTokenAccountBalance" => {
+            if let Some(o) = first.and_then(Value::as_str).and_then(|k| overrides.get(k)) {
+                token_balance(res, o);
+            }
+        }
+        "getProgramAccounts" => {
+            let program = first.and_then(Value::as_str).unwrap_or_default();
+            let filters = req
+                .params()
+                .get(1)
+                .and_then(|c| c.get("filters"))
+                .and_then(Value::as_array)
+                .cloned()
+                .unwrap_or_default();
+
+            let result = &mut res["result"];
+            let list = match result.get_mut("value") {
+                Some(v) => v,
+                None => result,
+            };
+            if let Value::Array(accounts) = list {
+                program_accounts(accounts, program, &filters, overrides, encoding);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Returns whether the upstream answered the request with an error instead of a
+/// result for an account that an override creates, as it does for token balances.
+pub fn creates(
+    res: &Value,
+    req: &RpcRequest,
+    overrides: &BTreeMap<String, AccountOverride>,
+) -> bool {
+    res.get("result").is_none()
+        && req.queried_accounts().iter().any(|k| {
+            overrides.get(*k).is_some_and(|o| {
+                !o.missing && (req.method != "getTokenAccountBalance" || o.data.is_some())
+            })
+        })
+}
+
+/// Replaces the upstream error in the argued response with
+/// an empty result at the slot for the overrides to be applied to.
+pub fn create(res: &mut Value, slot: u64) {
+    let id = res.get("id").cloned().unwrap_or(Value::Null);
+    *res = json!({
+        "jsonrpc": "2.0",
+        "result": {"context": {"slot": slot}, "value": null},
+        "id": id,
+    });
+}
+
+/// Builds a system account holding the argued lamports
+/// as the upstream reports it for the requested encoding.
+pub fn system_account(lamports: u64, encoding: Option<&str>) -> Value {
+    let o = AccountOverride {
+        lamports: Some(lamports),
+        ..Default::default()
+    };
+    account(&Value::Null, &o, encoding).unwrap_or_default()
+}
+
+/// Captures the argued upstream account as an override that reports it
+/// again, or reports it as missing if the upstream did not find it.
+pub fn capture(acc: &Value) -> AccountOverride {
+    match acc {
+        Value::Object(_) => AccountOverride {
+            missing: false,
+            lamports: acc["lamports"].as_u64(),
+            owner: acc["owner"].as_str().map(String::from),
+            data: data_bytes(acc).map(base64::encode),
+        },
+        _ => AccountOverride {
+            missing: true,
+            ..Default::default()
+        },
+    }
+}
+
+/// Returns the argued upstream account with the override applied, or `None`
+/// if the override reports the account as missing.
+fn account(upstream: &Value, o: &AccountOverride, encoding: Option<&str>) -> Option<Value> {
+    if o.missing {
+        return None;
+    }
+
+    let mut acc = match upstream {
+        Value::Object(_) => upstream.clone(),
+        _ => json!({
+            "data": encode(&[], encoding),
+            "executable": false,
+            "lamports": 0,
+            "owner": SYSTEM_PROGRAM,
+            "rentEpoch": 0,
+            "space": 0,
+        }),
+    };
+
+    if let Some(lamports) = o.lamports {
+        acc["lamports"] = json!(lamports);
+    }
+    if let Some(owner) = &o.owner {
+        acc["owner"] = json!(owner);
+    }
+    if let Some(bytes) = override_data(o) {
+        acc["data"] = encode(&bytes, encoding);
+        acc["space"] = json!(bytes.len());
+    }
+
+    Some(acc)
+}
+
+/// Reports the token amount held by an overridden token account, or the
+/// errors of the upstream for accounts that are missing or not token accounts.
+fn token_balance(res: &mut Value, o: &AccountOverride) {
+    let invalid = |res: &mut Value, message: &str| {
+        let id = res.get("id").cloned().unwrap_or(Value::Null);
+        *res = json!({
+            "jsonrpc": "2.0",
+            "error": {"code": INVALID_PARAMS, "message": message},
+            "id": id,
+        });
+    };
+
+    if o.missing {
+        return invalid(res, "Invalid param: could not find account");
+    }
+    if matches!(&o.owner, Some(owner) if !TOKEN_PROGRAMS.contains(&owner.as_str())) {
+        return invalid(res, "Invalid param: not a Token account");
+    }
+
+    let bytes = match override_data(o) {
+        Some(b) => b,
+        None => return,
+    };
+    let amount = match bytes.get(64..72) {
+        Some(a) => u64::from_le_bytes(a.try_into().unwrap()),
+        None => return invalid(res, "Invalid param: not a Token account"),
+    };
+
+    let value = &mut res["result"]["value"];
+    let decimals = value["decimals"].as_u64().unwrap_or_default();
+    let ui_amount = amount as f64 / 10f64.powi(decimals as i32);
+    value["amount"] = json!(amount.to_string());
+    value["decimals"] = json!(decimals);
+    value["uiAmount"] = json!(ui_amount);
+    value["uiAmountString"] = json!(ui_amount.to_string());
+}
+
+/// Applies the overrides to the `{ pubkey, account }` entries of a `getProgramAccounts`
+/// result, removing accounts that no longer match the program or filters and adding
+/// overridden accounts with data that now do.
+fn program_accounts(
+    accounts: &mut Vec<Value>,
+    program: &str,
+    filters: &[Value],
+    overrides: &BTreeMap<String, AccountOverride>,
+    encoding: Option<&str>,
+) {
+    let matches = |acc: &Value| {
+        acc["owner"] == program && data_bytes(acc).is_none_or(|d| passes(&d, filters))
+    };
+
+    accounts.retain_mut(|entry| {
+        let o = match entry["pubkey"].as_str().and_then(|k| overrides.get(k)) {
+            Some(o) => o,
+            None => return true,
+        };
+        match account(&entry["account"], o, encoding) {
+            Some(acc) if matches(&acc) => {
+                entry["account"] = acc;
+                true
+            }
+            _ => false,
+        }
+    });
+
+    for (key, o) in overrides {
+        let listed = accounts.iter().any(|e| e["pubkey"] == key.as_str());
+        if listed || o.data.is_none() || o.owner.as_deref() != Some(program) {
+            continue;
+        }
+        if let Some(acc) = account(&Value::Null, o, encoding).filter(|a| matches(a)) {
+            accounts.push(json!({"pubkey": key, "account": acc}));
+        }
+    }
+}
+
+/// Returns whether the account data passes every `dataSize` and `memcmp` filter.
+fn passes(data: &[u8], filters: &[Value]) -> bool {
+    filters.iter().all(|f| {
+        if let Some(size) = f.get("dataSize").and_then(Value::as_u64) {
+            return data.len() as u64 == size;
+        }
+        if let Some(cmp) = f.get("memcmp") {
+            let offset = cmp["offset"].as_u64().unwrap_or_default() as usize;
+            let bytes = cmp["bytes"].as_str().unwrap_or_default();
+            let expected = match cmp["encoding"].as_str() {
+                Some("base64") => base64::decode(bytes).unwrap_or_default(),
+                _ => bs58::decode(bytes).into_vec().unwrap_or_default(),
+            };
+            return match offset.checked_add(expected.len()) {
+                Some(end) => data.get(offset..end) == Some(expected.as_slice()),
+                None => false,
+            };
+        }
+        true
+    })
+}
+
+/// Returns the decoded account data of the override, if it sets any.
+fn override_data(o: &AccountOverride) -> Option<Vec<u8>> {
+    o.data
+        .as_deref()
+        .map(|d| base64::decode(d).unwrap_or_default())
+}
+
+/// Returns the decoded data of the argued account, unless it is parsed or compressed.
+fn data_bytes(acc: &Value) -> Option<Vec<u8>> {
+    match &acc["data"] {
+        Value::String(d) => bs58::decode(d).into_vec().ok(),
+        Value::Array(d) => match d.as_slice() {
+            [Value::String(d), enc] if enc == "base58" => bs58::decode(d).into_vec().ok(),
+            [Value::String(d), enc] if enc == "base64" => base64::decode(d).ok(),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Encodes the account data as the upstream would for the requested encoding,
+/// falling back to plain base-64 for parsed and compressed encodings.
+fn encode(bytes: &[u8], encoding: Option<&str>) -> Value {
+    match encoding {
+        Some("base58") => json!([bs58::encode(bytes).into_string(), "base58"]),
+        Some(_) => json!([base64::encode(bytes), "base64"]),
+        None => json!(bs58::encode(bytes).into_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: &str, params: Value) -> RpcRequest {
+        RpcRequest::parse(
+            1,
+            None,
+            json!({"jsonrpc": "2.0", "id": 1, "method": method, "params": params})
+                .to_string()
+                .into(),
+        )
+        .unwrap()
+    }
+
+    fn overrides() -> BTreeMap<String, AccountOverride> {
+        let mut token = vec![0; 165];
+        token[64..72].copy_from_slice(&2_500_000u64.to_le_bytes());
+
+        [
+            (
+                "closed".to_string(),
+                AccountOverride {
+                    missing: true,
+                    ..Default::default()
+                },
+            ),
+            (
+                "token".to_string(),
+                AccountOverride {
+                    lamports: Some(42),
+                    owner: Some(TOKEN_PROGRAMS[0].into()),
+                    data: Some(base64::encode(token)),
+                    ..Default::default()
+                },
+            ),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    #[test]
+    fn single_account_reads() {
+        let req = request("getAccountInfo", json!(["token", {"encoding": "base64"}]));
+        let mut res = json!({"result": {"context": {"slot": 1}, "value": null}, "id": 1});
+        apply(&mut res, &req, &overrides());
+        assert_eq!(res["result"]["value"]["lamports"], 42);
+        assert_eq!(res["result"]["value"]["owner"], TOKEN_PROGRAMS[0]);
+        assert_eq!(res["result"]["value"]["data"][1], "base64");
+        assert_eq!(res["result"]["value"]["space"], 165);
+
+        let req = request("getBalance", json!(["closed"]));
+        let mut res = json!({"result": {"context": {"slot": 1}, "value": 100}, "id": 1});
+        apply(&mut res, &req, &overrides());
+        assert_eq!(res["result"]["value"], 0);
+
+        let req = request("getMultipleAccounts", json!([["other", "closed"]]));
+        let mut res = json!({"result": {"context": {"slot": 1}, "value": [{"lamports": 1}, {"lamports": 2}]}});
+        apply(&mut res, &req, &overrides());
+        assert_eq!(res["result"]["value"], json!([{"lamports": 1}, null]));
+    }
+
+    #[test]
+    fn token_balances() {
+        let upstream = json!({
+            "jsonrpc": "2.0",
+            "result": {"context": {"slot": 1}, "value": {"amount": "1", "decimals": 6, "uiAmount": 0.000001, "uiAmountString": "0.000001"}},
+            "id": 1
+        });
+
+        let mut res = upstream.clone();
+        apply(
+            &mut res,
+            &request("getTokenAccountBalance", json!(["token"])),
+            &overrides(),
+        );
+        assert_eq!(res["result"]["value"]["amount"], "2500000");
+        assert_eq!(res["result"]["value"]["uiAmount"], 2.5);
+
+        let mut res = upstream;
+        apply(
+            &mut res,
+            &request("getTokenAccountBalance", json!(["closed"])),
+            &overrides(),
+        );
+        assert_eq!(res["error"]["code"], INVALID_PARAMS);
+        assert!(res.get("result").is_none());
+    }
+
+    #[test]
+    fn created_token_account() {
+        let not_found = json!({
+            "jsonrpc": "2.0",
+            "error": {"code": INVALID_PARAMS, "message": "Invalid param: could not find account"},
+            "id": 7
+        });
+        let req = request("getTokenAccountBalance", json!(["token"]));
+        assert!(!creates(
+            &not_found,
+            &request("getTokenAccountBalance", json!(["closed"])),
+            &overrides()
+        ));
+        assert!(creates(&not_found, &req, &overrides()));
+
+        let mut res = not_found;
+        create(&mut res, 5);
+        apply(&mut res, &req, &overrides());
+        assert_eq!(res["id"], 7);
+        assert!(res.get("error").is_none());
+        assert_eq!(res["result"]["context"]["slot"], 5);
+        assert_eq!(res["result"]["value"]["amount"], "2500000");
+        assert_eq!(res["result"]["value"]["decimals"], 0);
+        assert_eq!(res["result"]["value"]["uiAmountString"], "2500000");
+    }
+
+    #[test]
+    fn program_account_lists() {
+        let req = request(
+            "getProgramAccounts",
+            json!([TOKEN_PROGRAMS[0], {"encoding": "base64", "filters": [{"dataSize": 165}]}]),
+        );
+        let mut res = json!({"result": [
+            {"pubkey": "closed", "account": {"owner": TOKEN_PROGRAMS[0], "data": ["", "base64"]}},
+            {"pubkey": "other", "account": {"owner": TOKEN_PROGRAMS[0], "data": ["", "base64"]}}
+        ]});
+        apply(&mut res, &req, &overrides());
+
+        let keys: Vec<&str> = res["result"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| e["pubkey"].as_str().unwrap())
+            .collect();
+        assert_eq!(keys, vec!["other", "token"]);
+
+        let filtered = request(
+            "getProgramAccounts",
+            json!([TOKEN_PROGRAMS[0], {"filters": [{"dataSize": 82}]}]),
+        );
+        let mut res = json!({"result": []});
+        apply(&mut res, &filtered, &overrides());
+        assert_eq!(res["result"], json!([]));
+    }
+
+    #[test]
+    fn memcmp_filters() {
+        let memcmp = |offset: u64| [json!({"memcmp": {"offset": offset, "bytes": "2"}})];
+        assert!(passes(&[0, 1], &memcmp(1)));
+        assert!(!passes(&[0, 1], &memcmp(0)));
+        assert!(!passes(&[0, 1], &memcmp(2)));
+        assert!(!passes(&[0, 1], &memcmp(u64::MAX)));
+    }
+}
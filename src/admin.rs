@@ -0,0 +1,281 @@
+// Copyright (C) 2022 JET PROTOCOL HOLDINGS, LLC.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use actix_web::{delete, get, post, put, web, HttpResponse};
+
+use crate::config::AccountOverride;
+use crate::event_log::EventFilter;
+use crate::service::GlobalState;
+
+/// Returns the history of injected events as a JSON array,
+/// optionally filtered by the `method`, `event` and `since` query parameters.
+#[get("/admin/events")]
+pub async fn events(filter: web::Query<EventFilter>, data: web::Data<GlobalState>) -> HttpResponse {
+    HttpResponse::Ok().json(data.event_log.history(&filter))
+}
+
+/// Discards all of the recorded injected event history.
+#[delete("/admin/events")]
+pub async fn reset_events(data: web::Data<GlobalState>) -> HttpResponse {
+    data.event_log.clear_history();
+    HttpResponse::NoContent().finish()
+}
+
+/// Starts a simulated cluster halt that freezes the reported chain view.
+#[post("/admin/halt")]
+pub async fn halt(data: web::Data<GlobalState>) -> HttpResponse {
+    data.halt();
+    HttpResponse::NoContent().finish()
+}
+
+/// Ends the simulated cluster halt and resumes passing requests through.
+#[delete("/admin/halt")]
+pub async fn resume(data: web::Data<GlobalState>) -> HttpResponse {
+    data.resume();
+    HttpResponse::NoContent().finish()
+}
+
+/// Triggers a simulated validator restart that refuses connections and then
+/// reports the node as behind while it catches up.
+#[post("/admin/restart")]
+pub async fn restart(data: web::Data<GlobalState>) -> HttpResponse {
+    data.restart();
+    HttpResponse::NoContent().finish()
+}
+
+/// Returns the account overrides that are currently applied.
+#[get("/admin/accounts")]
+pub async fn accounts(data: web::Data<GlobalState>) -> HttpResponse {
+    HttpResponse::Ok().json(&*data.accounts.read().unwrap())
+}
+
+/// Overrides the state of the account with the argued pubkey.
+#[put("/admin/accounts/{pubkey}")]
+pub async fn set_account(
+    data: web::Data<GlobalState>,
+    pubkey: web::Path<String>,
+    body: web::Json<AccountOverride>,
+) -> HttpResponse {
+    data.accounts
+        .write()
+        .unwrap()
+        .insert(pubkey.into_inner(), body.into_inner());
+    HttpResponse::NoContent().finish()
+}
+
+/// Removes the override of the account with the argued pubkey.
+#[delete("/admin/accounts/{pubkey}")]
+pub async fn clear_account(
+    data: web::Data<GlobalState>,
+    pubkey: web::Path<String>,
+) -> HttpResponse {
+    data.accounts.write().unwrap().remove(pubkey.as_str());
+    HttpResponse::NoContent().finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::StatusCode;
+    use actix_web::{test, App};
+    use serde_json::{json, Value};
+    use std::sync::RwLock;
+
+    use super::*;
+    use crate::config::PercentageSettings;
+    use crate::event::NODE_UNHEALTHY;
+    use crate::mock::SLOT_DURATION;
+    use crate::service::rpc;
+    use crate::service::tests::{rpc_request, state_with_rules};
+
+    #[actix_web::test]
+    async fn query_and_reset_events() {
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(GlobalState {
+                    percentages: RwLock::new(PercentageSettings {
+                        tx_success: 0.0,
+                        ..PercentageSettings::all_success()
+                    }),
+                    ..Default::default()
+                }))
+                .service(events)
+                .service(reset_events)
+                .service(rpc),
+        )
+        .await;
+
+        let tx_req = rpc_request(json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "sendTransaction",
+            "params": [""]
+        }))
+        .to_request();
+
+        let tx_res: Value = test::call_and_read_body_json(&app, tx_req).await;
+
+        let history: Value = test::call_and_read_body_json(
+            &app,
+            test::TestRequest::get()
+                .uri("/admin/events?method=sendTransaction&event=FalsifiedSignature")
+                .to_request(),
+        )
+        .await;
+
+        assert_eq!(history.as_array().unwrap().len(), 1);
+        assert_eq!(history[0]["signature"], tx_res["result"]);
+
+        let filtered: Value = test::call_and_read_body_json(
+            &app,
+            test::TestRequest::get()
+                .uri("/admin/events?method=getSlot")
+                .to_request(),
+        )
+        .await;
+
+        assert_eq!(filtered, json!([]));
+
+        let reset = test::call_service(
+            &app,
+            test::TestRequest::delete()
+                .uri("/admin/events")
+                .to_request(),
+        )
+        .await;
+        assert_eq!(reset.status(), StatusCode::NO_CONTENT);
+
+        let cleared: Value = test::call_and_read_body_json(
+            &app,
+            test::TestRequest::get().uri("/admin/events").to_request(),
+        )
+        .await;
+
+        assert_eq!(cleared, json!([]));
+    }
+
+    #[actix_web::test]
+    async fn halt_and_resume() {
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(state_with_rules(Vec::new())))
+                .service(halt)
+                .service(resume)
+                .service(rpc),
+        )
+        .await;
+
+        let call = |method: &str| {
+            rpc_request(json!({"jsonrpc": "2.0", "id": 1, "method": method})).to_request()
+        };
+
+        let halted = test::call_service(
+            &app,
+            test::TestRequest::post().uri("/admin/halt").to_request(),
+        )
+        .await;
+        assert_eq!(halted.status(), StatusCode::NO_CONTENT);
+
+        let first: Value = test::call_and_read_body_json(&app, call("getSlot")).await;
+        tokio::time::sleep(SLOT_DURATION).await;
+        let hash: Value = test::call_and_read_body_json(&app, call("getLatestBlockhash")).await;
+        let epoch: Value = test::call_and_read_body_json(&app, call("getEpochInfo")).await;
+        let height: Value = test::call_and_read_body_json(&app, call("getBlockHeight")).await;
+        assert_eq!(hash["result"]["context"]["slot"], first["result"]);
+        assert_eq!(epoch["result"]["absoluteSlot"], first["result"]);
+        assert_eq!(height["result"], epoch["result"]["blockHeight"]);
+        tokio::time::sleep(SLOT_DURATION * 3).await;
+
+        let frozen: Value = test::call_and_read_body_json(&app, call("getSlot")).await;
+        assert_eq!(frozen["result"], first["result"]);
+        let same: Value = test::call_and_read_body_json(&app, call("getLatestBlockhash")).await;
+        assert_eq!(same["result"], hash["result"]);
+
+        let health: Value = test::call_and_read_body_json(&app, call("getHealth")).await;
+        assert_eq!(health["error"]["code"], NODE_UNHEALTHY);
+        assert!(health["error"]["data"]["numSlotsBehind"].as_u64().unwrap() >= 3);
+
+        let sent = test::call_service(&app, call("sendTransaction")).await;
+        assert_eq!(sent.headers().get("X-ATC-Event").unwrap(), "ClusterHalt");
+        let sent: Value = test::read_body_json(sent).await;
+        assert!(sent["result"].is_string());
+
+        let resumed = test::call_service(
+            &app,
+            test::TestRequest::delete().uri("/admin/halt").to_request(),
+        )
+        .await;
+        assert_eq!(resumed.status(), StatusCode::NO_CONTENT);
+
+        let current: Value = test::call_and_read_body_json(&app, call("getSlot")).await;
+        assert!(current["result"].as_u64().unwrap() >= first["result"].as_u64().unwrap() + 3);
+        let health: Value = test::call_and_read_body_json(&app, call("getHealth")).await;
+        assert_eq!(health["result"], "ok");
+    }
+
+    #[actix_web::test]
+    async fn override_accounts() {
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(state_with_rules(Vec::new())))
+                .service(accounts)
+                .service(set_account)
+                .service(clear_account)
+                .service(rpc),
+        )
+        .await;
+
+        let pubkey = "11111111111111111111111111111112";
+        let balance = || {
+            rpc_request(
+                json!({"jsonrpc": "2.0", "id": 1, "method": "getBalance", "params": [pubkey]}),
+            )
+            .to_request()
+        };
+
+        let original: Value = test::call_and_read_body_json(&app, balance()).await;
+
+        let set = test::call_service(
+            &app,
+            test::TestRequest::put()
+                .uri(&format!("/admin/accounts/{}", pubkey))
+                .set_json(json!({"lamports": 7}))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(set.status(), StatusCode::NO_CONTENT);
+
+        let overridden: Value = test::call_and_read_body_json(&app, balance()).await;
+        assert_eq!(overridden["result"]["value"], 7);
+
+        let listed: Value = test::call_and_read_body_json(
+            &app,
+            test::TestRequest::get().uri("/admin/accounts").to_request(),
+        )
+        .await;
+        assert_eq!(listed[pubkey]["lamports"], 7);
+        assert_eq!(listed[pubkey]["missing"], false);
+
+        test::call_service(
+            &app,
+            test::TestRequest::delete()
+                .uri(&format!("/admin/accounts/{}", pubkey))
+                .to_request(),
+        )
+        .await;
+
+        let restored: Value = test::call_and_read_body_json(&app, balance()).await;
+        assert_eq!(restored["result"]["value"], original["result"]["value"]);
+    }
+}
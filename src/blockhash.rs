@@ -0,0 +1,126 @@
+// Copyright (C) 2022 JET PROTOCOL HOLDINGS, LLC.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+/// Maximum number of distinct blockhashes remembered by the cache.
+const CACHE_CAPACITY: usize = 1_024;
+
+/// A blockhash returned by the upstream's `getLatestBlockhash` method.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SeenBlockhash {
+    pub slot: u64,
+    pub blockhash: String,
+    pub last_valid_block_height: u64,
+}
+
+impl SeenBlockhash {
+    /// Parses the blockhash from the result of a `getLatestBlockhash` response.
+    pub fn from_result(result: &Value) -> Option<Self> {
+        Some(Self {
+            slot: result.pointer("/context/slot")?.as_u64()?,
+            blockhash: result.pointer("/value/blockhash")?.as_str()?.to_string(),
+            last_valid_block_height: result.pointer("/value/lastValidBlockHeight")?.as_u64()?,
+        })
+    }
+}
+
+/// History of the blockhashes that passed through the proxy, which
+/// stale blockhash events hand out in place of the latest one.
+#[derive(Default)]
+pub struct BlockhashCache {
+    seen: Mutex<VecDeque<SeenBlockhash>>,
+}
+
+impl BlockhashCache {
+    /// Remembers the blockhash if it differs from the most recently seen one.
+    pub fn observe(&self, bh: SeenBlockhash) {
+        let mut seen = self.seen.lock().unwrap();
+        if seen.back().map(|b| &b.blockhash) == Some(&bh.blockhash) {
+            return;
+        }
+        if seen.len() >= CACHE_CAPACITY {
+            seen.pop_front();
+        }
+        seen.push_back(bh);
+    }
+
+    /// Returns the argued blockhash if it was seen by the proxy.
+    pub fn get(&self, blockhash: &str) -> Option<SeenBlockhash> {
+        self.seen
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .find(|b| b.blockhash == blockhash)
+            .cloned()
+    }
+
+    /// Returns the most recent blockhash that was seen at least
+    /// the argued number of slots before the current slot.
+    pub fn older_than(&self, slot: u64, age: u64) -> Option<SeenBlockhash> {
+        let cutoff = slot.checked_sub(age)?;
+        self.seen
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .find(|b| b.slot <= cutoff)
+            .cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn seen(slot: u64) -> SeenBlockhash {
+        SeenBlockhash {
+            slot,
+            blockhash: format!("hash{}", slot),
+            last_valid_block_height: slot + 150,
+        }
+    }
+
+    #[test]
+    fn parse_result() {
+        let result = json!({
+            "context": {"slot": 10},
+            "value": {"blockhash": "hash10", "lastValidBlockHeight": 160}
+        });
+        assert_eq!(SeenBlockhash::from_result(&result), Some(seen(10)));
+        assert_eq!(SeenBlockhash::from_result(&json!({"value": null})), None);
+    }
+
+    #[test]
+    fn stale_lookup() {
+        let cache = BlockhashCache::default();
+        for slot in [100, 200, 200, 400, 700] {
+            cache.observe(seen(slot));
+        }
+
+        assert_eq!(cache.seen.lock().unwrap().len(), 4);
+        assert_eq!(cache.older_than(700, 300), Some(seen(400)));
+        assert_eq!(cache.older_than(700, 550), Some(seen(100)));
+        assert_eq!(cache.older_than(700, 650), None);
+        assert_eq!(cache.older_than(100, 300), None);
+        assert_eq!(cache.get("hash200"), Some(seen(200)));
+        assert_eq!(cache.get("hash300"), None);
+    }
+}
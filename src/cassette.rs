@@ -0,0 +1,316 @@
+// Copyright (C) 2022 JET PROTOCOL HOLDINGS, LLC.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use actix_web::http::StatusCode;
+use actix_web::web;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::sync::Mutex;
+
+use crate::config::{CassetteMode, CassetteSettings, MatchRules};
+use crate::request::RpcRequest;
+
+/// A single recorded pair of an RPC request and the
+/// upstream response that was returned for it.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[cfg_attr(test, derive(PartialEq))]
+pub struct Interaction {
+    pub method: String,
+    pub params: Value,
+    pub status: u16,
+    pub response: Value,
+}
+
+impl Interaction {
+    /// Returns the recorded response body with the JSON-RPC
+    /// identifier replaced by the one from the argued request.
+    fn body_for(&self, req: &RpcRequest) -> web::Bytes {
+        match &self.response {
+            Value::String(s) => web::Bytes::from(s.clone()),
+            Value::Object(obj) => {
+                let mut obj = obj.clone();
+                if let Some(id) = req.body.get("id") {
+                    obj.insert("id".into(), id.clone());
+                }
+                web::Bytes::from(Value::Object(obj).to_string())
+            }
+            other => web::Bytes::from(other.to_string()),
+        }
+    }
+}
+
+/// Recorded interactions that share a match key and are replayed in
+/// the order they were recorded, repeating the last once exhausted.
+#[derive(Debug, Default)]
+struct Track {
+    interactions: Vec<Interaction>,
+    cursor: usize,
+}
+
+/// On-disk store of upstream interactions that are either appended
+/// to while recording or answered from while replaying.
+pub struct Cassette {
+    mode: CassetteMode,
+    rules: MatchRules,
+    file: Option<Mutex<File>>,
+    tracks: Mutex<HashMap<String, Track>>,
+}
+
+impl Cassette {
+    /// Returns the mode that the cassette was opened in.
+    pub fn mode(&self) -> CassetteMode {
+        self.mode
+    }
+
+    /// Appends the upstream response for the argued request to the cassette file.
+    pub fn record(
+        &self,
+        req: &RpcRequest,
+        status: StatusCode,
+        body: &[u8],
+    ) -> Result<(), std::io::Error> {
+        let response = serde_json::from_slice(body)
+            .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(body).into_owned()));
+
+        let interaction = Interaction {
+            method: req.method.clone(),
+            params: req.body.get("params").cloned().unwrap_or(Value::Null),
+            status: status.as_u16(),
+            response,
+        };
+
+        if let Some(file) = &self.file {
+            let mut f = file.lock().unwrap();
+            writeln!(f, "{}", serde_json::to_string(&interaction)?)?;
+            f.flush()?;
+        }
+
+        Ok(())
+    }
+
+    /// Finds the next recorded response that matches the argued request
+    /// according to the cassette's matching rules.
+    pub fn replay(&self, req: &RpcRequest) -> Option<(StatusCode, web::Bytes)> {
+        let key = self.key(&req.method, req.body.get("params").unwrap_or(&Value::Null));
+
+        let mut tracks = self.tracks.lock().unwrap();
+        let track = tracks.get_mut(&key)?;
+        let interaction = track
+            .interactions
+            .get(track.cursor.min(track.interactions.len() - 1))?;
+        track.cursor += 1;
+
+        Some((
+            StatusCode::from_u16(interaction.status).ok()?,
+            interaction.body_for(req),
+        ))
+    }
+
+    /// Adds the interaction to the in-memory track for its match key.
+    fn insert(&self, interaction: Interaction) {
+        let key = self.key(&interaction.method, &interaction.params);
+        self.tracks
+            .lock()
+            .unwrap()
+            .entry(key)
+            .or_default()
+            .interactions
+            .push(interaction);
+    }
+
+    /// Builds the lookup key for an RPC method and its parameters with
+    /// the ignored fields of any parameter objects removed.
+    fn key(&self, method: &str, params: &Value) -> String {
+        if !self.rules.params {
+            return method.to_string();
+        }
+
+        let params = match params {
+            Value::Array(items) => Value::Array(
+                items
+                    .iter()
+                    .map(|p| match p {
+                        Value::Object(obj) => Value::Object(
+                            obj.iter()
+                                .filter(|(k, _)| !self.rules.ignore_fields.contains(k))
+                                .map(|(k, v)| (k.clone(), v.clone()))
+                                .collect(),
+                        ),
+                        other => other.clone(),
+                    })
+                    .collect(),
+            ),
+            other => other.clone(),
+        };
+
+        format!("{}:{}", method, params)
+    }
+}
+
+impl TryFrom<&CassetteSettings> for Cassette {
+    type Error = std::io::Error;
+
+    fn try_from(s: &CassetteSettings) -> Result<Self, Self::Error> {
+        let mut cassette = Self {
+            mode: s.mode,
+            rules: s.matching.clone(),
+            file: None,
+            tracks: Mutex::new(HashMap::new()),
+        };
+
+        match s.mode {
+            CassetteMode::Record => {
+                let f = OpenOptions::new().create(true).append(true).open(&s.path)?;
+                cassette.file = Some(Mutex::new(f));
+            }
+            CassetteMode::Replay => {
+                let f = File::open(&s.path)?;
+                for line in BufReader::new(f).lines() {
+                    let line = line?;
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    cassette.insert(serde_json::from_str(&line)?);
+                }
+            }
+        }
+
+        Ok(cassette)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn request(id: u64, method: &str, params: Value) -> RpcRequest {
+        let payload = json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        })
+        .to_string();
+        RpcRequest::parse(id, None, payload.into()).unwrap()
+    }
+
+    fn settings(mode: CassetteMode, path: &str, matching: MatchRules) -> CassetteSettings {
+        CassetteSettings {
+            mode,
+            path: path.into(),
+            matching,
+        }
+    }
+
+    #[test]
+    fn record_then_replay() {
+        let path = std::env::temp_dir().join(format!("atc-cassette-{}.jsonl", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+
+        let recorder =
+            Cassette::try_from(&settings(CassetteMode::Record, path, MatchRules::default()))
+                .unwrap();
+
+        for slot in [10, 11] {
+            recorder
+                .record(
+                    &request(1, "getSlot", json!([])),
+                    StatusCode::OK,
+                    json!({"jsonrpc": "2.0", "result": slot, "id": 1})
+                        .to_string()
+                        .as_bytes(),
+                )
+                .unwrap();
+        }
+        recorder
+            .record(
+                &request(1, "getBalance", json!(["abc"])),
+                StatusCode::TOO_MANY_REQUESTS,
+                b"slow down",
+            )
+            .unwrap();
+        assert!(recorder.tracks.lock().unwrap().is_empty());
+
+        let player =
+            Cassette::try_from(&settings(CassetteMode::Replay, path, MatchRules::default()))
+                .unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        let replayed: Vec<Value> = (0..3)
+            .map(|_| {
+                let (_, body) = player.replay(&request(42, "getSlot", json!([]))).unwrap();
+                serde_json::from_slice(&body).unwrap()
+            })
+            .collect();
+
+        assert_eq!(
+            replayed[0],
+            json!({"jsonrpc": "2.0", "result": 10, "id": 42})
+        );
+        assert_eq!(replayed[1]["result"], 11);
+        assert_eq!(replayed[2]["result"], 11);
+
+        let (status, body) = player
+            .replay(&request(1, "getBalance", json!(["abc"])))
+            .unwrap();
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(body.as_ref(), b"slow down");
+
+        assert!(player
+            .replay(&request(1, "getBalance", json!(["xyz"])))
+            .is_none());
+    }
+
+    #[test]
+    fn matching_rules() {
+        let cassette = Cassette {
+            mode: CassetteMode::Replay,
+            rules: MatchRules {
+                params: true,
+                ignore_fields: vec!["commitment".into()],
+            },
+            file: None,
+            tracks: Mutex::new(HashMap::new()),
+        };
+
+        assert_eq!(
+            cassette.key("getBalance", &json!(["abc", {"commitment": "processed"}])),
+            cassette.key("getBalance", &json!(["abc", {"commitment": "finalized"}])),
+        );
+        assert_ne!(
+            cassette.key("getBalance", &json!(["abc"])),
+            cassette.key("getBalance", &json!(["xyz"])),
+        );
+
+        let method_only = Cassette {
+            rules: MatchRules {
+                params: false,
+                ignore_fields: Vec::new(),
+            },
+            ..cassette
+        };
+
+        assert_eq!(
+            method_only.key("getBalance", &json!(["abc"])),
+            method_only.key("getBalance", &json!(["xyz"])),
+        );
+    }
+}
@@ -13,7 +13,9 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
 use std::fs::read_to_string;
 use std::path::PathBuf;
 
@@ -26,6 +28,496 @@ pub struct Config {
     pub percentages: PercentageSettings,
     pub port: u16,
     pub workers: usize,
+    #[serde(default)]
+    pub event_log: EventLogSettings,
+    #[serde(default)]
+    pub cassette: Option<CassetteSettings>,
+    #[serde(default)]
+    pub mock_upstream: bool,
+    #[serde(default)]
+    pub faults: FaultSettings,
+    #[serde(default)]
+    pub mutations: MutationSettings,
+    #[serde(default)]
+    pub throttle: ThrottleSettings,
+    #[serde(default)]
+    pub drops: DropSettings,
+    #[serde(default)]
+    pub duplicates: DuplicateSettings,
+    #[serde(default)]
+    pub commitment: CommitmentSettings,
+    #[serde(default)]
+    pub preflight: PreflightSettings,
+    #[serde(default)]
+    pub failures: FailureSettings,
+    #[serde(default)]
+    pub congestion: CongestionSettings,
+    #[serde(default)]
+    pub blockhash: BlockhashSettings,
+    #[serde(default)]
+    pub restart: RestartSettings,
+    #[serde(default)]
+    pub views: ViewSettings,
+    #[serde(default)]
+    pub fork: ForkSettings,
+    #[serde(default)]
+    pub accounts: BTreeMap<String, AccountOverride>,
+    #[serde(default)]
+    pub airdrop: AirdropSettings,
+}
+
+/// Parameters of the mutation events that alter the results
+/// of responses passed through from the upstream.
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(default, rename_all = "camelCase")]
+pub struct MutationSettings {
+    pub drop_fields: Vec<String>,
+    pub truncate_length: Option<usize>,
+    pub corrupt_bytes: usize,
+    pub slot_lag: u64,
+}
+
+impl Default for MutationSettings {
+    fn default() -> Self {
+        Self {
+            drop_fields: vec![
+                "owner".into(),
+                "confirmationStatus".into(),
+                "lastValidBlockHeight".into(),
+            ],
+            truncate_length: None,
+            corrupt_bytes: 4,
+            slot_lag: 150,
+        }
+    }
+}
+
+/// Parameters of the stale blockhash event, where `stale_slots` is how
+/// many slots older than the latest one a handed out blockhash must be.
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(default, rename_all = "camelCase")]
+pub struct BlockhashSettings {
+    pub stale_slots: u64,
+}
+
+impl Default for BlockhashSettings {
+    fn default() -> Self {
+        Self { stale_slots: 300 }
+    }
+}
+
+/// Durations of the stages of a simulated validator restart, where the
+/// listener refuses connections for `down_ms` and the node then reports
+/// being behind for `catch_up_ms`, starting at `slots_behind` slots.
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(default, rename_all = "camelCase")]
+pub struct RestartSettings {
+    pub down_ms: u64,
+    pub catch_up_ms: u64,
+    pub slots_behind: u64,
+}
+
+impl Default for RestartSettings {
+    fn default() -> Self {
+        Self {
+            down_ms: 5_000,
+            catch_up_ms: 10_000,
+            slots_behind: 1_000,
+        }
+    }
+}
+
+/// Parameters of the airdrop failure event, which injects one of the
+/// `failures` into `requestAirdrop` requests failing `airdrop_success`,
+/// where a partial airdrop sends a `partial_fraction` of the requested lamports.
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(default, rename_all = "camelCase")]
+pub struct AirdropSettings {
+    pub failures: Vec<AirdropFailure>,
+    pub partial_fraction: f64,
+}
+
+impl Default for AirdropSettings {
+    fn default() -> Self {
+        Self {
+            failures: vec![
+                AirdropFailure::RateLimit,
+                AirdropFailure::LimitReached,
+                AirdropFailure::NeverLands,
+                AirdropFailure::Partial,
+            ],
+            partial_fraction: 0.5,
+        }
+    }
+}
+
+/// The ways that a faucet can fail a `requestAirdrop` request.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum AirdropFailure {
+    /// The faucet rejects the request with its HTTP 429 rate limit error.
+    RateLimit,
+    /// The faucet reports that the airdrop request limit was reached.
+    LimitReached,
+    /// A signature is returned for an airdrop that never lands.
+    NeverLands,
+    /// Only a fraction of the requested lamports is airdropped.
+    Partial,
+}
+
+/// Override of an account's state that is served in place of the upstream's,
+/// where `data` is base-64 encoded and unset fields keep the upstream values.
+/// A `missing` account is reported as if it did not exist.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(default, rename_all = "camelCase")]
+pub struct AccountOverride {
+    pub missing: bool,
+    pub lamports: Option<u64>,
+    pub owner: Option<String>,
+    pub data: Option<String>,
+}
+
+/// Parameters of the fork rollback event, where `rollback_rate` is the chance
+/// that a sent transaction lands on a fork that is later abandoned, and
+/// `rollback_ms` is how long the fork is visible at `processed`.
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(default, rename_all = "camelCase")]
+pub struct ForkSettings {
+    pub rollback_rate: f32,
+    pub rollback_ms: u64,
+}
+
+impl Default for ForkSettings {
+    fn default() -> Self {
+        Self {
+            rollback_rate: 0.5,
+            rollback_ms: 2_000,
+        }
+    }
+}
+
+/// Simulated backend nodes of the inconsistent reads event, where each entry
+/// of `lags_ms` is a view of the upstream that lags behind it by that many
+/// milliseconds, and requests are assigned to a view by the `assignment`.
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(default, rename_all = "camelCase")]
+pub struct ViewSettings {
+    pub lags_ms: Vec<u64>,
+    pub assignment: ViewAssignment,
+}
+
+impl Default for ViewSettings {
+    fn default() -> Self {
+        Self {
+            lags_ms: vec![0, 1_000, 5_000],
+            assignment: ViewAssignment::Random,
+        }
+    }
+}
+
+/// The ways that requests are assigned to the simulated backend views.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ViewAssignment {
+    /// Each request is assigned to a uniformly random view.
+    Random,
+    /// Requests are assigned to each view in turn.
+    RoundRobin,
+}
+
+/// Parameters of the congestion event, where `market_price` is the compute
+/// unit price in micro-lamports that transactions need to be included reliably
+/// and `fees` overrides the prioritization fee reported for specific accounts.
+/// Transactions paying less are missed with a probability of up to `miss_rate`,
+/// of which a `late_rate` fraction land late instead of being dropped.
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(default, rename_all = "camelCase")]
+pub struct CongestionSettings {
+    pub market_price: u64,
+    pub fees: BTreeMap<String, u64>,
+    pub miss_rate: f32,
+    pub late_rate: f32,
+}
+
+impl Default for CongestionSettings {
+    fn default() -> Self {
+        Self {
+            market_price: 10_000,
+            fees: BTreeMap::new(),
+            miss_rate: 0.9,
+            late_rate: 0.5,
+        }
+    }
+}
+
+/// Parameters of the event that reports landed transactions as failed,
+/// where `err` is the transaction error that their statuses report.
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(default, rename_all = "camelCase")]
+pub struct FailureSettings {
+    pub err: Value,
+}
+
+impl Default for FailureSettings {
+    fn default() -> Self {
+        Self {
+            err: json!({"InstructionError": [0, {"Custom": 6001}]}),
+        }
+    }
+}
+
+/// Parameters of the event that fails the preflight simulation of sent
+/// transactions with one of the `errors`, where the `logs` replace the
+/// program logs that are otherwise generated for instruction errors.
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(default, rename_all = "camelCase")]
+pub struct PreflightSettings {
+    pub errors: Vec<PreflightError>,
+    pub logs: Option<Vec<String>>,
+    pub units_consumed: u64,
+}
+
+impl Default for PreflightSettings {
+    fn default() -> Self {
+        Self {
+            errors: vec![
+                PreflightError::InstructionError { index: 0, code: 1 },
+                PreflightError::InsufficientFundsForFee,
+                PreflightError::AccountInUse,
+                PreflightError::ComputeBudgetExceeded { index: 0 },
+            ],
+            logs: None,
+            units_consumed: 200_000,
+        }
+    }
+}
+
+/// The transaction errors that a failed preflight simulation can report.
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum PreflightError {
+    /// The instruction at the index failed with a custom program error code.
+    InstructionError {
+        #[serde(default)]
+        index: u8,
+        code: u32,
+    },
+    /// The fee payer cannot afford the transaction fee.
+    InsufficientFundsForFee,
+    /// A writable account of the transaction is locked by another transaction.
+    AccountInUse,
+    /// The instruction at the index ran out of compute units.
+    ComputeBudgetExceeded {
+        #[serde(default)]
+        index: u8,
+    },
+}
+
+/// Parameters of the event that holds back the commitment of signature
+/// statuses, which are reported as at most `processed` for `processed_ms`
+/// after they are first seen and then as at most `confirmed` for a
+/// further `confirmed_ms`.
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(default, rename_all = "camelCase")]
+pub struct CommitmentSettings {
+    pub processed_ms: u64,
+    pub confirmed_ms: u64,
+}
+
+impl Default for CommitmentSettings {
+    fn default() -> Self {
+        Self {
+            processed_ms: 2_000,
+            confirmed_ms: 15_000,
+        }
+    }
+}
+
+/// Parameters of the events that drop sent transactions, where a dropped
+/// signature stays unknown until its blockhash expires, or until `expiry_ms`
+/// has passed if the proxy has not seen the blockhash, and a late transaction
+/// is forwarded upstream only after `landing_delay_ms`.
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(default, rename_all = "camelCase")]
+pub struct DropSettings {
+    pub expiry_ms: u64,
+    pub landing_delay_ms: u64,
+}
+
+impl Default for DropSettings {
+    fn default() -> Self {
+        Self {
+            expiry_ms: 90_000,
+            landing_delay_ms: 10_000,
+        }
+    }
+}
+
+/// Parameters of the event that submits a transaction to the upstream
+/// `count` times in total, with `spacing_ms` between each submission.
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(default, rename_all = "camelCase")]
+pub struct DuplicateSettings {
+    pub count: u32,
+    pub spacing_ms: u64,
+}
+
+impl Default for DuplicateSettings {
+    fn default() -> Self {
+        Self {
+            count: 2,
+            spacing_ms: 500,
+        }
+    }
+}
+
+/// Parameters of the throttle event that streams responses
+/// passed through from the upstream at a limited bandwidth.
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(default, rename_all = "camelCase")]
+pub struct ThrottleSettings {
+    pub bytes_per_second: u64,
+    pub chunk_size: usize,
+    pub jitter: f32,
+}
+
+impl Default for ThrottleSettings {
+    fn default() -> Self {
+        Self {
+            bytes_per_second: 16 * 1024,
+            chunk_size: 1024,
+            jitter: 0.0,
+        }
+    }
+}
+
+/// Selection settings for the registered faults that are injected, where
+/// `weights` picks the fault for requests failing the RPC success rate and
+/// each of the `rules` injects its fault into matching requests at a rate.
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(default, rename_all = "camelCase")]
+pub struct FaultSettings {
+    pub weights: BTreeMap<String, u32>,
+    pub rules: Vec<FaultRule>,
+}
+
+/// A rule for injecting the named fault into a fraction of the requests
+/// for the listed methods, or for all methods if none are listed, which can
+/// be narrowed to transactions invoking the listed programs or paid for by
+/// the listed fee payers.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(rename_all = "camelCase")]
+pub struct FaultRule {
+    pub fault: String,
+    #[serde(default)]
+    pub methods: Vec<String>,
+    #[serde(default)]
+    pub programs: Vec<String>,
+    #[serde(default)]
+    pub fee_payers: Vec<String>,
+    pub rate: f32,
+}
+
+impl Default for FaultSettings {
+    fn default() -> Self {
+        Self {
+            weights: ["Latency", "RateLimit", "Timeout"]
+                .into_iter()
+                .map(|name| (name.to_string(), 1))
+                .collect(),
+            rules: Vec::new(),
+        }
+    }
+}
+
+/// Settings for recording upstream traffic to a cassette
+/// file or replaying responses from one instead of an upstream.
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(rename_all = "camelCase")]
+pub struct CassetteSettings {
+    pub mode: CassetteMode,
+    pub path: String,
+    #[serde(default)]
+    pub matching: MatchRules,
+}
+
+/// Whether the cassette file is being written to or read from.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum CassetteMode {
+    Record,
+    Replay,
+}
+
+/// Rules for matching incoming requests to recorded interactions, which
+/// are always matched by method and optionally by their parameters.
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(default, rename_all = "camelCase")]
+pub struct MatchRules {
+    pub params: bool,
+    pub ignore_fields: Vec<String>,
+}
+
+impl Default for MatchRules {
+    fn default() -> Self {
+        Self {
+            params: true,
+            ignore_fields: Vec::new(),
+        }
+    }
+}
+
+/// Output destination and verbosity of the structured
+/// log of the proxy's fault decisions.
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(rename_all = "camelCase")]
+pub struct EventLogSettings {
+    pub output: String,
+    pub verbosity: LogVerbosity,
+}
+
+/// The levels of detail for the structured event log, where
+/// each level includes everything logged by the levels below it.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "camelCase")]
+pub enum LogVerbosity {
+    /// Nothing is logged.
+    Off,
+    /// Only requests that had an event injected are logged.
+    Faults,
+    /// Every request is logged.
+    All,
+    /// Every request is logged along with its parameters.
+    Debug,
+}
+
+impl Default for EventLogSettings {
+    fn default() -> Self {
+        Self {
+            output: "stdout".into(),
+            verbosity: LogVerbosity::Faults,
+        }
+    }
 }
 
 /// Percentage details for success rates for incoming RPC
@@ -36,6 +528,13 @@ pub struct Config {
 pub struct PercentageSettings {
     pub rpc_success: f32,
     pub tx_success: f32,
+    #[serde(default = "full_success")]
+    pub airdrop_success: f32,
+}
+
+/// Success rate of policies that inject no faults unless they are configured.
+fn full_success() -> f32 {
+    1.0
 }
 
 impl Default for PercentageSettings {
@@ -43,6 +542,7 @@ impl Default for PercentageSettings {
         Self {
             rpc_success: 0.65,
             tx_success: 0.8,
+            airdrop_success: 1.0,
         }
     }
 }
@@ -54,6 +554,24 @@ impl Default for Config {
             percentages: Default::default(),
             port: 8080,
             workers: 10,
+            event_log: Default::default(),
+            cassette: None,
+            mock_upstream: false,
+            faults: Default::default(),
+            mutations: Default::default(),
+            throttle: Default::default(),
+            drops: Default::default(),
+            duplicates: Default::default(),
+            commitment: Default::default(),
+            preflight: Default::default(),
+            failures: Default::default(),
+            congestion: Default::default(),
+            blockhash: Default::default(),
+            restart: Default::default(),
+            views: Default::default(),
+            fork: Default::default(),
+            accounts: Default::default(),
+            airdrop: Default::default(),
         }
     }
 }
@@ -71,7 +589,7 @@ impl TryFrom<&str> for Config {
     type Error = serde_json::Error;
 
     fn try_from(s: &str) -> Result<Self, Self::Error> {
-        serde_json::from_str(s).map_err(Into::into)
+        serde_json::from_str(s)
     }
 }
 
@@ -91,9 +609,31 @@ mod tests {
                 percentages: PercentageSettings {
                     rpc_success: 0.65,
                     tx_success: 0.8,
+                    airdrop_success: 1.0,
                 },
                 port: 8080,
-                workers: 10
+                workers: 10,
+                event_log: EventLogSettings {
+                    output: "stdout".into(),
+                    verbosity: LogVerbosity::Faults,
+                },
+                cassette: None,
+                mock_upstream: false,
+                faults: Default::default(),
+                mutations: Default::default(),
+                throttle: Default::default(),
+                drops: Default::default(),
+                duplicates: Default::default(),
+                commitment: Default::default(),
+                preflight: Default::default(),
+                failures: Default::default(),
+                congestion: Default::default(),
+                blockhash: Default::default(),
+                restart: Default::default(),
+                views: Default::default(),
+                fork: Default::default(),
+                accounts: Default::default(),
+                airdrop: Default::default(),
             }
         );
     }
@@ -107,10 +647,29 @@ mod tests {
                 rpc_endpoint: "http://localhost:8899".into(),
                 percentages: PercentageSettings {
                     rpc_success: 1.0,
-                    tx_success: 0.5
+                    tx_success: 0.5,
+                    airdrop_success: 1.0,
                 },
                 port: 8080,
-                workers: 10
+                workers: 10,
+                event_log: Default::default(),
+                cassette: None,
+                mock_upstream: false,
+                faults: Default::default(),
+                mutations: Default::default(),
+                throttle: Default::default(),
+                drops: Default::default(),
+                duplicates: Default::default(),
+                commitment: Default::default(),
+                preflight: Default::default(),
+                failures: Default::default(),
+                congestion: Default::default(),
+                blockhash: Default::default(),
+                restart: Default::default(),
+                views: Default::default(),
+                fork: Default::default(),
+                accounts: Default::default(),
+                airdrop: Default::default(),
             }
         );
     }
@@ -124,10 +683,29 @@ mod tests {
                 rpc_endpoint: "http://localhost:8899".into(),
                 percentages: PercentageSettings {
                     rpc_success: 1.0,
-                    tx_success: 0.5
+                    tx_success: 0.5,
+                    airdrop_success: 1.0,
                 },
                 port: 8080,
-                workers: 10
+                workers: 10,
+                event_log: Default::default(),
+                cassette: None,
+                mock_upstream: false,
+                faults: Default::default(),
+                mutations: Default::default(),
+                throttle: Default::default(),
+                drops: Default::default(),
+                duplicates: Default::default(),
+                commitment: Default::default(),
+                preflight: Default::default(),
+                failures: Default::default(),
+                congestion: Default::default(),
+                blockhash: Default::default(),
+                restart: Default::default(),
+                views: Default::default(),
+                fork: Default::default(),
+                accounts: Default::default(),
+                airdrop: Default::default(),
             }
         );
     }
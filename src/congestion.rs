@@ -0,0 +1,184 @@
+// Copyright (C) 2022 JET PROTOCOL HOLDINGS, LLC.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use rand::Rng;
+use serde_json::{json, Value};
+
+use crate::config::CongestionSettings;
+use crate::transaction::Transaction;
+
+/// Number of recent slots that `getRecentPrioritizationFees` reports fees for.
+const RECENT_SLOTS: u64 = 150;
+
+/// What happens to a transaction sent into the congested fee market.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Included,
+    Dropped,
+    Late,
+}
+
+/// Returns the probability that the argued transaction is missed by the
+/// leaders, which falls linearly from `miss_rate` for a transaction without
+/// a compute unit price to zero for one paying at least the highest configured
+/// fee of its accounts, or the market price if none of them are configured.
+pub fn miss_probability(tx: &Transaction, settings: &CongestionSettings) -> f64 {
+    let accounts: Vec<&str> = tx.account_keys.iter().map(String::as_str).collect();
+    let fee = going_fee(&accounts, settings);
+    let price = tx.compute_unit_price().unwrap_or(0);
+    if fee == 0 || price >= fee {
+        return 0.0;
+    }
+
+    let shortfall = 1.0 - price as f64 / fee as f64;
+    settings.miss_rate.clamp(0.0, 1.0) as f64 * shortfall
+}
+
+/// Draws what happens to the argued transaction in the congested fee market.
+pub fn outcome<R: Rng + ?Sized>(
+    tx: &Transaction,
+    settings: &CongestionSettings,
+    rng: &mut R,
+) -> Outcome {
+    if !rng.gen_bool(miss_probability(tx, settings)) {
+        Outcome::Included
+    } else if rng.gen_bool(settings.late_rate.clamp(0.0, 1.0) as f64) {
+        Outcome::Late
+    } else {
+        Outcome::Dropped
+    }
+}
+
+/// Builds the `getRecentPrioritizationFees` result for the argued accounts up to
+/// the current slot, where each slot reports a fee that varies around the highest
+/// configured fee of the accounts, or the market price if none are configured.
+pub fn recent_fees<R: Rng + ?Sized>(
+    accounts: &[&str],
+    slot: u64,
+    settings: &CongestionSettings,
+    rng: &mut R,
+) -> Value {
+    let fee = going_fee(accounts, settings);
+
+    let first = slot.saturating_sub(RECENT_SLOTS - 1);
+    let fees: Vec<Value> = (first..=slot)
+        .map(|s| {
+            let varied = (fee as f64 * rng.gen_range(0.5..=1.5)) as u64;
+            json!({"slot": s, "prioritizationFee": varied})
+        })
+        .collect();
+
+    json!(fees)
+}
+
+/// Returns the highest configured fee of the argued accounts,
+/// or the market price if none of them are configured.
+fn going_fee(accounts: &[&str], settings: &CongestionSettings) -> u64 {
+    accounts
+        .iter()
+        .filter_map(|a| settings.fees.get(*a))
+        .max()
+        .copied()
+        .unwrap_or(settings.market_price)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::thread_rng;
+
+    use super::*;
+    use crate::transaction::tests::wire_transaction_with;
+    use crate::transaction::COMPUTE_BUDGET_PROGRAM;
+
+    fn priced_transaction(price: Option<u64>) -> Transaction {
+        let budget: [u8; 32] = bs58::decode(COMPUTE_BUDGET_PROGRAM)
+            .into_vec()
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let data = price.map(|p| [&[3], &p.to_le_bytes()[..]].concat());
+        let ixs: Vec<(u8, &[u8])> = data.iter().map(|d| (1, d.as_slice())).collect();
+        Transaction::decode(&wire_transaction_with([1; 64], &[[2; 32], budget], &ixs)).unwrap()
+    }
+
+    #[test]
+    fn miss_probabilities() {
+        let settings = CongestionSettings {
+            market_price: 1_000,
+            miss_rate: 0.5,
+            ..Default::default()
+        };
+
+        assert_eq!(miss_probability(&priced_transaction(None), &settings), 0.5);
+        assert_eq!(
+            miss_probability(&priced_transaction(Some(500)), &settings),
+            0.25
+        );
+        assert_eq!(
+            miss_probability(&priced_transaction(Some(1_000)), &settings),
+            0.0
+        );
+
+        let mut rng = thread_rng();
+        for _ in 0..20 {
+            assert_eq!(
+                outcome(&priced_transaction(Some(2_000)), &settings, &mut rng),
+                Outcome::Included
+            );
+        }
+    }
+
+    #[test]
+    fn hot_account_miss_probabilities() {
+        let settings = CongestionSettings {
+            market_price: 1_000,
+            miss_rate: 0.5,
+            fees: [(bs58::encode([2; 32]).into_string(), 10_000)]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            miss_probability(&priced_transaction(Some(5_000)), &settings),
+            0.25
+        );
+        assert_eq!(
+            miss_probability(&priced_transaction(Some(10_000)), &settings),
+            0.0
+        );
+    }
+
+    #[test]
+    fn recent_fee_entries() {
+        let settings = CongestionSettings {
+            market_price: 1_000,
+            fees: [("hot".to_string(), 50_000)].into_iter().collect(),
+            ..Default::default()
+        };
+
+        let hot = recent_fees(&["cold", "hot"], 500, &settings, &mut thread_rng());
+        let entries = hot.as_array().unwrap();
+        assert_eq!(entries.len(), 150);
+        assert_eq!(entries[149]["slot"], 500);
+        assert!(entries
+            .iter()
+            .all(|e| (25_000..=75_000).contains(&e["prioritizationFee"].as_u64().unwrap())));
+
+        let cold = recent_fees(&[], 10, &settings, &mut thread_rng());
+        assert_eq!(cold.as_array().unwrap().len(), 11);
+        assert!(cold[0]["prioritizationFee"].as_u64().unwrap() <= 1_500);
+    }
+}
@@ -13,14 +13,36 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use actix_web::body::SizedStream;
 use actix_web::http::header::{HeaderName, HeaderValue};
-use actix_web::{web, HttpResponse};
+use actix_web::{web, HttpResponse, HttpResponseBuilder};
+use async_trait::async_trait;
+use futures_util::{future, stream, Stream, StreamExt};
 use rand::distributions::{Distribution, Standard};
 use rand::{thread_rng, Rng};
-use serde_json::json;
+use serde_json::{json, Value};
+use std::collections::hash_map::Entry;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
 use std::str::FromStr;
+use std::time::Instant;
+use tokio::time::Duration;
 
-use crate::service::{passthrough, GlobalState};
+use crate::account::{self, OVERRIDDEN_METHODS};
+use crate::blockhash::SeenBlockhash;
+use crate::config::{AccountOverride, AirdropFailure, CommitmentSettings, ThrottleSettings};
+use crate::congestion::{self, Outcome};
+use crate::fault::Fault;
+use crate::halt::{Snapshot, FROZEN_METHODS};
+use crate::mock::SLOT_DURATION;
+use crate::mutation;
+use crate::preflight;
+use crate::request::{Reason, RpcRequest};
+use crate::restart::Stage;
+use crate::service::{forward, passthrough, GlobalState};
+use crate::transaction::Transaction;
+use crate::view::{self, SEEN_RETENTION};
 
 /// Enum declaraction to define and implement the logic
 /// for various types of Solana RPC and transaction failure
@@ -28,11 +50,40 @@ use crate::service::{passthrough, GlobalState};
 #[derive(Clone, Debug)]
 #[cfg_attr(test, derive(PartialEq))]
 pub enum RpcEvent {
+    AirdropFailure,
+    ClusterHalt,
+    CommitmentLag,
+    Congestion,
+    ConnectionReset,
+    CorruptData,
+    DropFields,
+    DroppedTransaction,
+    DuplicateTransaction,
+    EmptyResponse,
+    FailedTransaction,
     FalsifiedSignature,
+    ForkRollback,
+    Hang,
+    HtmlError,
+    InconsistentReads,
+    InvalidJson,
+    LateTransaction,
     Latency,
+    LostResponse,
+    NullValues,
+    PreflightFailure,
     RateLimit,
+    ReorderResults,
+    StaleBlockhash,
+    StaleSlot,
+    StallBody,
+    Throttle,
     Timeout,
+    TruncateArrays,
+    TruncatedBody,
     UnconfirmedSignature,
+    ValidatorRestart,
+    WrongContentLength,
 }
 
 impl RpcEvent {
@@ -58,30 +109,237 @@ impl RpcEvent {
     /// native `rand::random` function to generate a random variant.
     ///
     /// ```
+    /// # use atc::event::RpcEvent;
     /// let event: RpcEvent = rand::random();
     /// ```
     pub fn random() -> Self {
         rand::random()
     }
 
+    /// Returns every variant of `RpcEvent`.
+    pub fn all() -> Vec<Self> {
+        vec![
+            RpcEvent::AirdropFailure,
+            RpcEvent::ClusterHalt,
+            RpcEvent::CommitmentLag,
+            RpcEvent::Congestion,
+            RpcEvent::ConnectionReset,
+            RpcEvent::CorruptData,
+            RpcEvent::DropFields,
+            RpcEvent::DroppedTransaction,
+            RpcEvent::DuplicateTransaction,
+            RpcEvent::EmptyResponse,
+            RpcEvent::FailedTransaction,
+            RpcEvent::FalsifiedSignature,
+            RpcEvent::ForkRollback,
+            RpcEvent::Hang,
+            RpcEvent::HtmlError,
+            RpcEvent::InconsistentReads,
+            RpcEvent::InvalidJson,
+            RpcEvent::LateTransaction,
+            RpcEvent::Latency,
+            RpcEvent::LostResponse,
+            RpcEvent::NullValues,
+            RpcEvent::PreflightFailure,
+            RpcEvent::RateLimit,
+            RpcEvent::ReorderResults,
+            RpcEvent::StaleBlockhash,
+            RpcEvent::StaleSlot,
+            RpcEvent::StallBody,
+            RpcEvent::Throttle,
+            RpcEvent::Timeout,
+            RpcEvent::TruncateArrays,
+            RpcEvent::TruncatedBody,
+            RpcEvent::UnconfirmedSignature,
+            RpcEvent::ValidatorRestart,
+            RpcEvent::WrongContentLength,
+        ]
+    }
+
+    /// Returns the name of the variant, which is used to refer to
+    /// it in configuration and in the `X-ATC-Event` response header.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RpcEvent::AirdropFailure => "AirdropFailure",
+            RpcEvent::ClusterHalt => "ClusterHalt",
+            RpcEvent::CommitmentLag => "CommitmentLag",
+            RpcEvent::Congestion => "Congestion",
+            RpcEvent::ConnectionReset => "ConnectionReset",
+            RpcEvent::CorruptData => "CorruptData",
+            RpcEvent::DropFields => "DropFields",
+            RpcEvent::DroppedTransaction => "DroppedTransaction",
+            RpcEvent::DuplicateTransaction => "DuplicateTransaction",
+            RpcEvent::EmptyResponse => "EmptyResponse",
+            RpcEvent::FailedTransaction => "FailedTransaction",
+            RpcEvent::FalsifiedSignature => "FalsifiedSignature",
+            RpcEvent::ForkRollback => "ForkRollback",
+            RpcEvent::Hang => "Hang",
+            RpcEvent::HtmlError => "HtmlError",
+            RpcEvent::InconsistentReads => "InconsistentReads",
+            RpcEvent::InvalidJson => "InvalidJson",
+            RpcEvent::LateTransaction => "LateTransaction",
+            RpcEvent::Latency => "Latency",
+            RpcEvent::LostResponse => "LostResponse",
+            RpcEvent::NullValues => "NullValues",
+            RpcEvent::PreflightFailure => "PreflightFailure",
+            RpcEvent::RateLimit => "RateLimit",
+            RpcEvent::ReorderResults => "ReorderResults",
+            RpcEvent::StaleBlockhash => "StaleBlockhash",
+            RpcEvent::StaleSlot => "StaleSlot",
+            RpcEvent::StallBody => "StallBody",
+            RpcEvent::Throttle => "Throttle",
+            RpcEvent::Timeout => "Timeout",
+            RpcEvent::TruncateArrays => "TruncateArrays",
+            RpcEvent::TruncatedBody => "TruncatedBody",
+            RpcEvent::UnconfirmedSignature => "UnconfirmedSignature",
+            RpcEvent::ValidatorRestart => "ValidatorRestart",
+            RpcEvent::WrongContentLength => "WrongContentLength",
+        }
+    }
+
+    /// Returns whether the event can be injected into the argued request, where
+    /// events that only change certain methods or need configured failures or
+    /// a halt in progress decline every other request.
+    pub fn applies(&self, req: &RpcRequest, data: &GlobalState) -> bool {
+        match self {
+            RpcEvent::AirdropFailure => {
+                req.method == "requestAirdrop" && !data.airdrop.failures.is_empty()
+            }
+            RpcEvent::ClusterHalt => {
+                data.halted().is_some()
+                    && (matches!(req.method.as_str(), "getHealth" | "sendTransaction")
+                        || FROZEN_METHODS.contains(&req.method.as_str()))
+            }
+            RpcEvent::DroppedTransaction | RpcEvent::LateTransaction => req.transaction.is_some(),
+            RpcEvent::ForkRollback => match req.method.as_str() {
+                "sendTransaction" => req.transaction.is_some(),
+                "getSignatureStatuses" => true,
+                m => OVERRIDDEN_METHODS.contains(&m) && req.commitment() == Some("processed"),
+            },
+            RpcEvent::PreflightFailure => {
+                req.method == "sendTransaction"
+                    && !preflight::skips_preflight(req.params())
+                    && !data.preflight.errors.is_empty()
+            }
+            _ => true,
+        }
+    }
+
     /// The `HttpResponse` responder for each variant of `RpcEvent` to define how
     /// they should interact with the incoming request and shared application data
     /// and defines the event type's RPC or transaction interception behavior prior
     /// to responding to the incoming request itself.
     pub async fn respond(
         &self,
-        payload: &web::Bytes,
+        req: &RpcRequest,
         data: &web::Data<GlobalState>,
     ) -> Result<HttpResponse, Box<dyn std::error::Error>> {
         let mut rng = thread_rng();
+        let id = req.id();
 
         match self {
+            RpcEvent::AirdropFailure => self.fail_airdrop(req, data).await,
+            RpcEvent::ClusterHalt => self.halt(req, data).await,
+            RpcEvent::CommitmentLag => self.lag_commitment(req, data).await,
+            RpcEvent::ForkRollback => self.roll_back(req, data).await,
+            RpcEvent::InconsistentReads => self.read_view(req, data).await,
+            RpcEvent::ValidatorRestart => self.restart(req, data).await,
+            RpcEvent::Congestion => self.congest(req, data).await,
+            RpcEvent::ConnectionReset => Ok(HttpResponse::Ok()
+                .insert_header(("X-ATC-Event", "ConnectionReset"))
+                .insert_header(UNENCODED)
+                .content_type("application/json")
+                .streaming(cut_off(web::Bytes::new()))),
+            RpcEvent::DroppedTransaction | RpcEvent::LateTransaction => {
+                self.drop_transaction(req, data).await
+            }
+            RpcEvent::DuplicateTransaction => {
+                let res = passthrough(req, data).await?;
+                let spacing = Duration::from_millis(data.duplicates.spacing_ms);
+                let copies = data.duplicates.count.saturating_sub(1) as usize;
+                self.forward_later(req, data, vec![spacing; copies]);
+                Ok(res)
+            }
+            RpcEvent::EmptyResponse => Ok(HttpResponse::Ok()
+                .insert_header(("X-ATC-Event", "EmptyResponse"))
+                .insert_header(UNENCODED)
+                .content_type("application/json")
+                .finish()),
+            RpcEvent::Hang => {
+                // The handler never returns, so the request is logged up front.
+                data.event_log.record(req, None);
+                future::pending().await
+            }
+            RpcEvent::HtmlError => {
+                let (mut res, title) = if rng.gen_bool(0.5) {
+                    (HttpResponse::BadGateway(), "502 Bad Gateway")
+                } else {
+                    (HttpResponse::ServiceUnavailable(), "503 Service Temporarily Unavailable")
+                };
+
+                Ok(res
+                    .insert_header(("X-ATC-Event", "HtmlError"))
+                    .insert_header(UNENCODED)
+                    .content_type("text/html")
+                    .body(nginx_error_page(title)))
+            }
+            RpcEvent::InvalidJson => Ok(HttpResponse::Ok()
+                .insert_header(("X-ATC-Event", "InvalidJson"))
+                .insert_header(UNENCODED)
+                .content_type("application/json")
+                .body(format!(
+                    r#"{{"jsonrpc": "2.0", "result": {{'value': NaN, "context": {{"slot": }},}}, "id": {}"#,
+                    id
+                ))),
+            RpcEvent::StaleBlockhash => self.stale_blockhash(req, data).await,
+            RpcEvent::StallBody => Ok(HttpResponse::Ok()
+                .insert_header(("X-ATC-Event", "StallBody"))
+                .insert_header(UNENCODED)
+                .content_type("application/json")
+                .streaming(stream::pending::<Result<web::Bytes, io::Error>>())),
+            RpcEvent::TruncatedBody => {
+                let (status, body) = forward(req, data).await?;
+                let head = body.slice(..body.len() / 2);
+
+                Ok(HttpResponseBuilder::new(status)
+                    .insert_header(("X-ATC-Event", "TruncatedBody"))
+                    .insert_header(UNENCODED)
+                    .content_type("application/json")
+                    .streaming(cut_off(head)))
+            }
+            RpcEvent::WrongContentLength => {
+                let (status, body) = forward(req, data).await?;
+                let len = body.len() as u64;
+
+                // Either understate the length so the body is cut short, or
+                // overstate it so the connection closes before it is complete.
+                let declared = if len > 1 && rng.gen_bool(0.5) {
+                    rng.gen_range(1..len)
+                } else {
+                    len + rng.gen_range(1..=len.max(1))
+                };
+
+                Ok(HttpResponseBuilder::new(status)
+                    .insert_header(("X-ATC-Event", "WrongContentLength"))
+                    .insert_header(UNENCODED)
+                    .content_type("application/json")
+                    .body(SizedStream::new(
+                        declared,
+                        stream::once(future::ok::<_, io::Error>(body)),
+                    )))
+            }
+            RpcEvent::CorruptData
+            | RpcEvent::DropFields
+            | RpcEvent::NullValues
+            | RpcEvent::ReorderResults
+            | RpcEvent::StaleSlot
+            | RpcEvent::TruncateArrays => self.mutate(req, data).await,
+            RpcEvent::FailedTransaction => self.spoof_failure(req, data).await,
             RpcEvent::FalsifiedSignature => {
                 let sig = generate_fake_signature(&mut rng);
 
-                let mut fake_sigs = data.fake_signatures.write().unwrap();
-                dbg!(&fake_sigs);
-                fake_sigs.push(sig.clone());
+                data.fake_signatures.write().unwrap().push(sig.clone());
+                req.set_signature(&sig);
 
                 Ok(HttpResponse::Ok()
                     .insert_header(("X-ATC-Event", "FalsifiedSignature"))
@@ -90,31 +348,88 @@ impl RpcEvent {
                         json!({
                             "jsonrpc": "2.0",
                             "result": sig,
-                            "id": 1,
+                            "id": id,
                         })
                         .to_string(),
                     ))
             }
             RpcEvent::Latency => {
-                tokio::time::sleep(tokio::time::Duration::from_secs(rng.gen_range(5..=10))).await;
-                let mut res = passthrough(payload, data).await?;
+                self.delay(Duration::from_secs(rng.gen_range(5..=10)), req, data)
+                    .await;
+                let mut res = passthrough(req, data).await?;
                 res.headers_mut().insert(
                     HeaderName::from_str("X-ATC-Event")?,
                     HeaderValue::from_str("Latency")?,
                 );
                 Ok(res)
             }
+            RpcEvent::LostResponse => {
+                // The upstream response is discarded after it succeeds, so the
+                // client sees a network error for a request that was processed.
+                forward(req, data).await?;
+                Ok(HttpResponse::Ok()
+                    .insert_header(("X-ATC-Event", "LostResponse"))
+                    .insert_header(UNENCODED)
+                    .content_type("application/json")
+                    .streaming(cut_off(web::Bytes::new())))
+            }
+            RpcEvent::PreflightFailure => {
+                let errors = &data.preflight.errors;
+                if req.method != "sendTransaction"
+                    || preflight::skips_preflight(req.params())
+                    || errors.is_empty()
+                {
+                    return passthrough(req, data).await;
+                }
+
+                let error = &errors[rng.gen_range(0..errors.len())];
+                let failure =
+                    preflight::simulation_failure(error, req.transaction.as_ref(), &data.preflight);
+
+                Ok(HttpResponse::Ok()
+                    .insert_header(("X-ATC-Event", "PreflightFailure"))
+                    .content_type("application/json")
+                    .body(
+                        json!({
+                            "jsonrpc": "2.0",
+                            "error": failure,
+                            "id": id,
+                        })
+                        .to_string(),
+                    ))
+            }
             RpcEvent::RateLimit => Ok(HttpResponse::TooManyRequests()
                 .insert_header(("X-ATC-Event", "RateLimit"))
                 .finish()),
+            RpcEvent::Throttle => {
+                let (status, body) = forward(req, data).await?;
+                let len = body.len() as u64;
+                let chunks = throttle_schedule(body, &data.throttle, &mut rng);
+
+                let total = chunks.iter().map(|(_, d)| *d).sum();
+                self.observe_delay(total, req, data);
+                req.set_streaming(total);
+
+                let drip = stream::iter(chunks).then(|(chunk, d)| async move {
+                    tokio::time::sleep(d).await;
+                    Ok::<_, io::Error>(chunk)
+                });
+
+                Ok(HttpResponseBuilder::new(status)
+                    .insert_header(("X-ATC-Event", "Throttle"))
+                    .insert_header(UNENCODED)
+                    .content_type("application/json")
+                    .body(SizedStream::new(len, drip)))
+            }
             RpcEvent::Timeout => {
-                tokio::time::sleep(tokio::time::Duration::from_secs(rng.gen_range(15..=20))).await;
+                self.delay(Duration::from_secs(rng.gen_range(15..=20)), req, data)
+                    .await;
                 Ok(HttpResponse::RequestTimeout()
                     .insert_header(("X-ATC-Event", "Timeout"))
                     .finish())
             }
             RpcEvent::UnconfirmedSignature => {
-                tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+                self.delay(Duration::from_secs(5), req, data).await;
                 Ok(HttpResponse::Ok()
                     .insert_header(("X-ATC-Event", "UnconfirmedSignature"))
                     .content_type("application/json")
@@ -128,13 +443,616 @@ impl RpcEvent {
                                 },
                                 "value": [null]
                             },
-                            "id": 1
+                            "id": id
                         })
                         .to_string(),
                     ))
             }
         }
     }
+
+    /// Fails a `requestAirdrop` request with one of the configured faucet failures,
+    /// passing through any other request.
+    async fn fail_airdrop(
+        &self,
+        req: &RpcRequest,
+        data: &web::Data<GlobalState>,
+    ) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+        let failures = &data.airdrop.failures;
+        if req.method != "requestAirdrop" || failures.is_empty() {
+            return passthrough(req, data).await;
+        }
+
+        let id = req.id();
+        let error = |code: i64, message: &str| {
+            json!({
+                "jsonrpc": "2.0",
+                "error": {"code": code, "message": message},
+                "id": id,
+            })
+            .to_string()
+        };
+
+        let failure = failures[thread_rng().gen_range(0..failures.len())];
+        let res = match failure {
+            AirdropFailure::RateLimit => HttpResponse::TooManyRequests()
+                .content_type("application/json")
+                .body(error(429, FAUCET_RATE_LIMIT)),
+            AirdropFailure::LimitReached => HttpResponse::Ok()
+                .content_type("application/json")
+                .body(error(INTERNAL_ERROR, "airdrop request limit reached")),
+            AirdropFailure::NeverLands => {
+                let sig = generate_fake_signature(&mut thread_rng());
+                req.set_signature(&sig);
+                HttpResponse::Ok()
+                    .content_type("application/json")
+                    .body(json!({"jsonrpc": "2.0", "result": sig, "id": id}).to_string())
+            }
+            AirdropFailure::Partial => {
+                let mut body = req.body.clone();
+                if let Some(lamports) = body.pointer_mut("/params/1") {
+                    let requested = lamports.as_u64().unwrap_or_default();
+                    *lamports = json!((requested as f64 * data.airdrop.partial_fraction) as u64);
+                }
+                let partial = RpcRequest::parse(req.id, req.client, body.to_string().into())?;
+                let (status, body) = forward(&partial, data).await?;
+                req.set_upstream_status(status.as_u16());
+                HttpResponseBuilder::new(status)
+                    .content_type("application/json")
+                    .body(body)
+            }
+        };
+
+        Ok(res)
+    }
+
+    /// Answers the request from the frozen chain view of the simulated cluster halt,
+    /// where sent transactions are never forwarded and the node reports being
+    /// behind. Methods that are not affected by the halt are passed through, as
+    /// are all requests if no halt is in progress or the chain view cannot be
+    /// probed, such as when replaying.
+    async fn halt(
+        &self,
+        req: &RpcRequest,
+        data: &web::Data<GlobalState>,
+    ) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+        let halt = match data.halted() {
+            Some(h) => h,
+            None => return passthrough(req, data).await,
+        };
+
+        let id = req.id();
+        let body = match req.method.as_str() {
+            "getHealth" => node_behind(id, halt.slots_behind().max(1)),
+            "sendTransaction" => {
+                let sig = match &req.transaction {
+                    Some(tx) => tx.signature().to_string(),
+                    None => generate_fake_signature(&mut thread_rng()),
+                };
+                req.set_signature(&sig);
+                json!({"jsonrpc": "2.0", "result": sig, "id": id})
+            }
+            m if FROZEN_METHODS.contains(&m) => {
+                let snapshot = match halt.snapshot() {
+                    Some(s) => s,
+                    None => {
+                        let epoch_info = probe_result("getEpochInfo", data).await;
+                        let blockhash = probe_result("getLatestBlockhash", data).await;
+                        match (epoch_info, blockhash) {
+                            (Some(e), Some(b)) => halt.freeze(Snapshot::new(e, b)),
+                            _ => return passthrough(req, data).await,
+                        }
+                    }
+                };
+                match snapshot.result(m) {
+                    Some(result) => json!({"jsonrpc": "2.0", "result": result, "id": id}),
+                    None => return passthrough(req, data).await,
+                }
+            }
+            _ => return passthrough(req, data).await,
+        };
+
+        Ok(HttpResponse::Ok()
+            .insert_header(("X-ATC-Event", "ClusterHalt"))
+            .content_type("application/json")
+            .body(body.to_string()))
+    }
+
+    /// Plays the stages of a simulated validator restart, triggering one if none
+    /// is in progress. Connections are reset while the validator is down and every
+    /// request is answered as unhealthy while it catches up with the cluster.
+    async fn restart(
+        &self,
+        req: &RpcRequest,
+        data: &web::Data<GlobalState>,
+    ) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+        let stage = match data.restart_stage() {
+            Some(s) => s,
+            None => {
+                data.restart();
+                Stage::Down
+            }
+        };
+
+        let id = req.id();
+        match stage {
+            Stage::Down => Ok(HttpResponse::Ok()
+                .insert_header(("X-ATC-Event", "ValidatorRestart"))
+                .insert_header(UNENCODED)
+                .content_type("application/json")
+                .streaming(cut_off(web::Bytes::new()))),
+            Stage::CatchingUp { slots_behind } => Ok(HttpResponse::Ok()
+                .insert_header(("X-ATC-Event", "ValidatorRestart"))
+                .content_type("application/json")
+                .body(node_behind(id, slots_behind).to_string())),
+            Stage::Done => passthrough(req, data).await,
+        }
+    }
+
+    /// Forwards the request to the upstream and answers it as one of the simulated
+    /// backend views would, which lags behind the upstream by its configured delay.
+    /// Slots of a lagging view are moved back and statuses that the upstream first
+    /// reported more recently than the lag are hidden, so that consecutive requests
+    /// assigned to different views can see the chain move backwards.
+    async fn read_view(
+        &self,
+        req: &RpcRequest,
+        data: &web::Data<GlobalState>,
+    ) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+        let index = view::assign(&data.views, &data.view_counter, &mut thread_rng());
+        let lag = Duration::from_millis(data.views.lags_ms.get(index).copied().unwrap_or_default());
+
+        let (status, body) = forward(req, data).await?;
+        let mut res: Value = match serde_json::from_slice(&body) {
+            Ok(v) => v,
+            Err(_) => return Ok(HttpResponseBuilder::new(status).body(body)),
+        };
+
+        if let Some(result) = res.get_mut("result") {
+            let sigs = req.queried_signatures();
+            let mut seen = data.view_seen.write().unwrap();
+            view::hide_unseen(result, &req.method, &sigs, &mut seen, lag);
+            view::age(result, &req.method, view::lag_slots(lag));
+        }
+
+        Ok(HttpResponseBuilder::new(status)
+            .insert_header(("X-ATC-Event", "InconsistentReads"))
+            .insert_header(("X-ATC-View", index.to_string()))
+            .content_type("application/json")
+            .body(res.to_string()))
+    }
+
+    /// Lands sent transactions on simulated forks that are abandoned at the configured
+    /// rate. A transaction on such a fork is forwarded and reported as `processed`
+    /// until the fork is abandoned, after which it is unknown and account reads at
+    /// `processed` commitment report the accounts it changed as they were before
+    /// it landed, until it lands again on the surviving fork.
+    async fn roll_back(
+        &self,
+        req: &RpcRequest,
+        data: &web::Data<GlobalState>,
+    ) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+        if req.method == "sendTransaction" {
+            let tx = match &req.transaction {
+                Some(tx) => tx,
+                None => return passthrough(req, data).await,
+            };
+            let sig = tx.signature().to_string();
+
+            // Retries of a forked transaction are answered by the fork it landed on.
+            if data.forks.is_forked(&sig, &data.fork) {
+                req.set_signature(&sig);
+                return Ok(HttpResponse::Ok()
+                    .insert_header(("X-ATC-Event", "ForkRollback"))
+                    .content_type("application/json")
+                    .body(json!({"jsonrpc": "2.0", "result": sig, "id": req.id()}).to_string()));
+            }
+
+            if thread_rng().gen::<f32>() >= data.fork.rollback_rate {
+                return passthrough(req, data).await;
+            }
+
+            let previous = prior_accounts(tx, data).await;
+            let (status, body) = forward(req, data).await?;
+            let landed = serde_json::from_slice::<Value>(&body)
+                .map(|res| res["result"] == sig)
+                .unwrap_or_default();
+            if landed {
+                data.forks.abandon(&sig, current_slot(data).await, previous);
+            }
+
+            return Ok(HttpResponseBuilder::new(status)
+                .insert_header(("X-ATC-Event", "ForkRollback"))
+                .content_type("application/json")
+                .body(body));
+        }
+
+        let accounts = OVERRIDDEN_METHODS.contains(&req.method.as_str())
+            && req.commitment() == Some("processed");
+        let statuses = req.method == "getSignatureStatuses";
+        if !accounts && !statuses {
+            return passthrough(req, data).await;
+        }
+
+        let (status, body) = forward(req, data).await?;
+        let mut res: Value = match serde_json::from_slice(&body) {
+            Ok(v) => v,
+            Err(_) => return Ok(HttpResponseBuilder::new(status).body(body)),
+        };
+
+        if statuses {
+            if let Some(Value::Array(values)) = res.pointer_mut("/result/value") {
+                for (sig, value) in req.queried_signatures().into_iter().zip(values) {
+                    if let Some(forked) = data.forks.status(sig, &data.fork) {
+                        *value = forked;
+                    }
+                }
+            }
+        } else {
+            // Configured overrides are served in place of the fork's state.
+            let mut reverted = data.forks.reverted(&data.fork);
+            reverted.retain(|key, _| !data.accounts.read().unwrap().contains_key(key));
+            account::apply(&mut res, req, &reverted);
+        }
+
+        Ok(HttpResponseBuilder::new(status)
+            .insert_header(("X-ATC-Event", "ForkRollback"))
+            .content_type("application/json")
+            .body(res.to_string()))
+    }
+
+    /// Forwards a `getSignatureStatuses` request to the upstream and holds back the
+    /// commitment of each returned status based on when the proxy first saw it.
+    async fn lag_commitment(
+        &self,
+        req: &RpcRequest,
+        data: &web::Data<GlobalState>,
+    ) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+        let (status, body) = forward(req, data).await?;
+
+        let mut res: Value = match serde_json::from_slice(&body) {
+            Ok(v) => v,
+            Err(_) => return Ok(HttpResponseBuilder::new(status).body(body)),
+        };
+
+        let sigs = req.queried_signatures();
+
+        if let Some(Value::Array(statuses)) = res.pointer_mut("/result/value") {
+            let settings = &data.commitment;
+            let lifetime = Duration::from_millis(settings.processed_ms + settings.confirmed_ms);
+            let now = Instant::now();
+
+            // Released signatures are remembered past the lifetime, so that they
+            // are not held back again once their commitment has been released.
+            let mut seen = data.commitment_seen.write().unwrap();
+            seen.retain(|_, t| now.duration_since(*t) < lifetime + SEEN_RETENTION);
+
+            for (sig, status) in sigs.iter().zip(statuses.iter_mut()) {
+                if status.is_null() {
+                    continue;
+                }
+                match seen.get(*sig) {
+                    Some(first) => hold_commitment(status, now.duration_since(*first), settings),
+                    None if !lifetime.is_zero() => {
+                        seen.insert(sig.to_string(), now);
+                        hold_commitment(status, Duration::ZERO, settings);
+                    }
+                    None => {}
+                }
+            }
+        }
+
+        Ok(HttpResponseBuilder::new(status)
+            .insert_header(("X-ATC-Event", "CommitmentLag"))
+            .content_type("application/json")
+            .body(res.to_string()))
+    }
+
+    /// Forwards the request to the upstream and makes the blockhash details of
+    /// the response outdated: `getLatestBlockhash` returns a previously seen
+    /// blockhash that is at least the configured number of slots old, or the
+    /// latest one with a `lastValidBlockHeight` that has already passed, while
+    /// `getFeeForMessage` returns no fee and `isBlockhashValid` returns false.
+    async fn stale_blockhash(
+        &self,
+        req: &RpcRequest,
+        data: &web::Data<GlobalState>,
+    ) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+        let (status, body) = forward(req, data).await?;
+
+        let mut res: Value = match serde_json::from_slice(&body) {
+            Ok(v) => v,
+            Err(_) => return Ok(HttpResponseBuilder::new(status).body(body)),
+        };
+
+        let stale_slots = data.blockhash.stale_slots;
+        match (req.method.as_str(), res.get_mut("result")) {
+            ("getLatestBlockhash", Some(result)) => {
+                if let Some(latest) = SeenBlockhash::from_result(result) {
+                    let stale = data
+                        .blockhashes
+                        .older_than(latest.slot, stale_slots)
+                        .unwrap_or(SeenBlockhash {
+                            last_valid_block_height: latest
+                                .last_valid_block_height
+                                .saturating_sub(stale_slots),
+                            ..latest
+                        });
+
+                    result["value"] = json!({
+                        "blockhash": stale.blockhash,
+                        "lastValidBlockHeight": stale.last_valid_block_height,
+                    });
+                }
+            }
+            ("getFeeForMessage", Some(result)) if result.get("value").is_some() => {
+                result["value"] = Value::Null;
+            }
+            ("isBlockhashValid", Some(result)) if result.get("value").is_some() => {
+                result["value"] = json!(false);
+            }
+            _ => {}
+        }
+
+        Ok(HttpResponseBuilder::new(status)
+            .insert_header(("X-ATC-Event", "StaleBlockhash"))
+            .content_type("application/json")
+            .body(res.to_string()))
+    }
+
+    /// Answers `getRecentPrioritizationFees` from the simulated fee market and
+    /// drops or delays sent transactions whose compute unit price falls short of
+    /// it, passing through every other request.
+    async fn congest(
+        &self,
+        req: &RpcRequest,
+        data: &web::Data<GlobalState>,
+    ) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+        let mut rng = thread_rng();
+        let settings = &data.congestion;
+
+        match (req.method.as_str(), &req.transaction) {
+            ("getRecentPrioritizationFees", _) => {
+                let accounts: Vec<&str> = req
+                    .params()
+                    .first()
+                    .and_then(Value::as_array)
+                    .map(|a| a.iter().filter_map(Value::as_str).collect())
+                    .unwrap_or_default();
+
+                let slot = current_slot(data).await;
+                let fees = congestion::recent_fees(&accounts, slot, settings, &mut rng);
+
+                Ok(HttpResponse::Ok()
+                    .insert_header(("X-ATC-Event", "Congestion"))
+                    .content_type("application/json")
+                    .body(
+                        json!({
+                            "jsonrpc": "2.0",
+                            "result": fees,
+                            "id": req.id(),
+                        })
+                        .to_string(),
+                    ))
+            }
+            ("sendTransaction", Some(tx)) => match congestion::outcome(tx, settings, &mut rng) {
+                Outcome::Included => passthrough(req, data).await,
+                Outcome::Dropped => {
+                    RpcEvent::DroppedTransaction
+                        .drop_transaction(req, data)
+                        .await
+                }
+                Outcome::Late => RpcEvent::LateTransaction.drop_transaction(req, data).await,
+            },
+            _ => passthrough(req, data).await,
+        }
+    }
+
+    /// Forwards a sent transaction to the upstream and marks its signature to be
+    /// reported as failed, then reports the configured `err` in the responses of
+    /// `getSignatureStatuses` and `getTransaction` requests for marked signatures.
+    async fn spoof_failure(
+        &self,
+        req: &RpcRequest,
+        data: &web::Data<GlobalState>,
+    ) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+        if req.method == "sendTransaction" {
+            if let Some(tx) = &req.transaction {
+                let now = Instant::now();
+                let mut failed = data.failed_signatures.write().unwrap();
+                failed.retain(|_, sent| now.duration_since(*sent) < SEEN_RETENTION);
+                failed.insert(tx.signature().to_string(), now);
+            }
+            return passthrough(req, data).await;
+        }
+
+        let (status, body) = forward(req, data).await?;
+        let mut res: Value = match serde_json::from_slice(&body) {
+            Ok(v) => v,
+            Err(_) => return Ok(HttpResponseBuilder::new(status).body(body)),
+        };
+
+        if let Some(result) = res.get_mut("result") {
+            let marked: Vec<bool> = req
+                .queried_signatures()
+                .iter()
+                .map(|s| data.is_failed(s))
+                .collect();
+            mutation::fail_transactions(result, &req.method, &marked, &data.failures.err);
+        }
+
+        Ok(HttpResponseBuilder::new(status)
+            .insert_header(("X-ATC-Event", "FailedTransaction"))
+            .content_type("application/json")
+            .body(res.to_string()))
+    }
+
+    /// Responds with the real signature of the sent transaction without forwarding
+    /// it upstream, so that a dropped transaction stays unknown until its blockhash
+    /// expires, while a late transaction is forwarded in the background once
+    /// the configured landing delay has passed. Requests without a decodable
+    /// transaction are passed through so the upstream can reject them.
+    async fn drop_transaction(
+        &self,
+        req: &RpcRequest,
+        data: &web::Data<GlobalState>,
+    ) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+        let tx = match &req.transaction {
+            Some(tx) => tx,
+            None => return passthrough(req, data).await,
+        };
+        let sig = tx.signature().to_string();
+
+        let late = matches!(self, RpcEvent::LateTransaction);
+        let delay = data.drops.landing_delay_ms;
+        let until = if late {
+            Instant::now() + Duration::from_millis(delay)
+        } else {
+            blockhash_expiry(tx, data).await
+        };
+        let now = Instant::now();
+
+        // Retries of a signature that is already dropped keep its original deadline.
+        let fresh = {
+            let mut dropped = data.dropped_signatures.write().unwrap();
+            dropped.retain(|_, t| *t > now);
+            match dropped.entry(sig.clone()) {
+                Entry::Occupied(_) => false,
+                Entry::Vacant(e) => {
+                    e.insert(until);
+                    true
+                }
+            }
+        };
+
+        if late && fresh {
+            self.forward_later(req, data, vec![Duration::from_millis(delay)]);
+        }
+
+        req.set_signature(&sig);
+
+        Ok(HttpResponse::Ok()
+            .insert_header(("X-ATC-Event", self.as_str()))
+            .content_type("application/json")
+            .body(
+                json!({
+                    "jsonrpc": "2.0",
+                    "result": sig,
+                    "id": req.id(),
+                })
+                .to_string(),
+            ))
+    }
+
+    /// Forwards copies of the request to the upstream in the background after each
+    /// of the argued delays, recording every copy in the event log on its own.
+    fn forward_later(
+        &self,
+        req: &RpcRequest,
+        data: &web::Data<GlobalState>,
+        delays: Vec<Duration>,
+    ) {
+        let (event, data, request_id) = (self.clone(), data.clone(), req.id);
+        let (client, payload) = (req.client, req.payload.clone());
+
+        actix_web::rt::spawn(async move {
+            for d in delays {
+                tokio::time::sleep(d).await;
+                let copy =
+                    match RpcRequest::parse(data.event_log.next_id(), client, payload.clone()) {
+                        Ok(r) => r,
+                        Err(_) => return,
+                    };
+
+                copy.decide(&event, Reason::Delayed { request_id });
+                if let Some(tx) = &copy.transaction {
+                    copy.set_signature(tx.signature());
+                }
+
+                let res = forward(&copy, &data).await;
+                data.event_log
+                    .record(&copy, res.err().map(|e| e.to_string()));
+            }
+        });
+    }
+
+    /// Forwards the request to the upstream and alters the `result` of its
+    /// JSON-RPC response according to the mutation event variant, leaving
+    /// error responses and bodies that are not valid JSON untouched.
+    async fn mutate(
+        &self,
+        req: &RpcRequest,
+        data: &web::Data<GlobalState>,
+    ) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+        let (status, body) = forward(req, data).await?;
+
+        let mut res: Value = match serde_json::from_slice(&body) {
+            Ok(v) => v,
+            Err(_) => return Ok(HttpResponseBuilder::new(status).body(body)),
+        };
+
+        if let Some(result) = res.get_mut("result") {
+            let settings = &data.mutations;
+            let mut rng = thread_rng();
+
+            match self {
+                RpcEvent::CorruptData => mutation::corrupt_data(result, settings, &mut rng),
+                RpcEvent::DropFields => mutation::drop_fields(result, settings),
+                RpcEvent::NullValues => mutation::null_values(result),
+                RpcEvent::ReorderResults => mutation::reorder(result, &mut rng),
+                RpcEvent::StaleSlot => mutation::stale_slot(result, settings),
+                RpcEvent::TruncateArrays => mutation::truncate_arrays(result, settings),
+                _ => {}
+            }
+        }
+
+        Ok(HttpResponseBuilder::new(status)
+            .insert_header(("X-ATC-Event", self.as_str()))
+            .content_type("application/json")
+            .body(res.to_string()))
+    }
+
+    /// Sleeps for the argued duration and records it as an injected
+    /// delay for the event in the request trace and proxy metrics.
+    async fn delay(&self, duration: Duration, req: &RpcRequest, data: &web::Data<GlobalState>) {
+        tokio::time::sleep(duration).await;
+        self.observe_delay(duration, req, data);
+    }
+
+    /// Records the argued duration as an injected delay for the
+    /// event in the request trace and proxy metrics.
+    fn observe_delay(&self, duration: Duration, req: &RpcRequest, data: &web::Data<GlobalState>) {
+        req.add_delay(duration);
+        data.metrics
+            .injected_latency
+            .with_label_values(&[&self.to_string()])
+            .observe(duration.as_secs_f64());
+    }
+}
+
+impl fmt::Display for RpcEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[async_trait(?Send)]
+impl Fault for RpcEvent {
+    fn name(&self) -> &str {
+        self.as_str()
+    }
+
+    fn applies(&self, req: &RpcRequest, data: &GlobalState) -> bool {
+        RpcEvent::applies(self, req, data)
+    }
+
+    async fn respond(
+        &self,
+        req: &RpcRequest,
+        data: &web::Data<GlobalState>,
+    ) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+        RpcEvent::respond(self, req, data).await
+    }
 }
 
 impl Distribution<RpcEvent> for Standard {
@@ -147,6 +1065,191 @@ impl Distribution<RpcEvent> for Standard {
     }
 }
 
+/// JSON-RPC error code of an internal error, which the faucet's failures are reported as.
+const INTERNAL_ERROR: i64 = -32603;
+
+/// Error message of the public faucets when their rate limit is exceeded.
+const FAUCET_RATE_LIMIT: &str = "You've either reached your airdrop limit today or the airdrop faucet has run dry. Please visit https://faucet.solana.com for alternate sources of test SOL";
+
+/// JSON-RPC error code of a node that is unhealthy or behind the cluster.
+pub const NODE_UNHEALTHY: i64 = -32005;
+
+/// Returns the JSON-RPC error response of a node that is behind the cluster
+/// by the argued number of slots.
+fn node_behind(id: Value, slots: u64) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "error": {
+            "code": NODE_UNHEALTHY,
+            "message": format!("Node is behind by {} slots", slots),
+            "data": {"numSlotsBehind": slots},
+        },
+        "id": id,
+    })
+}
+
+/// Header that stops the compression middleware from re-encoding a broken
+/// response, which would otherwise hide its framing from the client.
+const UNENCODED: (&str, &str) = ("Content-Encoding", "identity");
+
+/// Creates a response body stream that yields the argued bytes and then fails,
+/// causing the server to drop the connection before the response is complete.
+fn cut_off(head: web::Bytes) -> impl Stream<Item = Result<web::Bytes, io::Error>> {
+    let reset = async {
+        // Give the connection a moment to flush the headers and bytes sent so far.
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        Err(io::Error::new(
+            io::ErrorKind::ConnectionReset,
+            "connection reset by proxy",
+        ))
+    };
+
+    stream::iter((!head.is_empty()).then(|| Ok(head))).chain(stream::once(reset))
+}
+
+/// Splits the argued body into chunks of the configured size, each paired with
+/// the time it takes to send at the configured bandwidth, randomly scaled by
+/// up to the configured jitter fraction in either direction.
+fn throttle_schedule<R: Rng + ?Sized>(
+    body: web::Bytes,
+    settings: &ThrottleSettings,
+    rng: &mut R,
+) -> Vec<(web::Bytes, Duration)> {
+    let size = settings.chunk_size.max(1);
+    let rate = settings.bytes_per_second.max(1) as f64;
+    let jitter = settings.jitter.clamp(0.0, 1.0) as f64;
+
+    (0..body.len())
+        .step_by(size)
+        .map(|start| {
+            let chunk = body.slice(start..(start + size).min(body.len()));
+            let scale = 1.0 + rng.gen_range(-jitter..=jitter);
+            let secs = chunk.len() as f64 / rate * scale;
+            (chunk, Duration::from_secs_f64(secs))
+        })
+        .collect()
+}
+
+/// Returns the current slot of the upstream, or zero if it cannot be probed.
+pub async fn current_slot(data: &web::Data<GlobalState>) -> u64 {
+    probe_result("getSlot", data)
+        .await
+        .and_then(|r| r.as_u64())
+        .unwrap_or_default()
+}
+
+/// Returns the result of the argued parameterless method from the upstream,
+/// or `None` if it cannot be probed or answers with an error.
+async fn probe_result(method: &str, data: &web::Data<GlobalState>) -> Option<Value> {
+    let body = json!({"jsonrpc": "2.0", "id": 1, "method": method});
+    let req = RpcRequest::parse(0, None, body.to_string().into()).ok()?;
+
+    match data.upstream.probe(&req).await {
+        Ok(Some((_, body))) => serde_json::from_slice::<Value>(&body)
+            .ok()
+            .and_then(|mut v| v.get_mut("result").map(Value::take)),
+        _ => None,
+    }
+}
+
+/// Returns when the blockhash of the argued transaction expires, estimated from the
+/// `lastValidBlockHeight` that the upstream reported for it and the current block
+/// height, or once `drops.expiryMs` has passed if the blockhash was not seen by the
+/// proxy or the block height cannot be probed.
+async fn blockhash_expiry(tx: &Transaction, data: &web::Data<GlobalState>) -> Instant {
+    let fallback = Instant::now() + Duration::from_millis(data.drops.expiry_ms);
+    let last_valid = match data.blockhashes.get(&tx.recent_blockhash) {
+        Some(bh) => bh.last_valid_block_height,
+        None => return fallback,
+    };
+
+    match probe_result("getBlockHeight", data)
+        .await
+        .and_then(|h| h.as_u64())
+    {
+        Some(height) => {
+            let blocks = last_valid.saturating_sub(height);
+            Instant::now() + SLOT_DURATION * u32::try_from(blocks).unwrap_or(u32::MAX)
+        }
+        None => fallback,
+    }
+}
+
+/// Reads the state of the accounts that the argued transaction may change, other than
+/// the programs that it invokes, from the upstream before the transaction is sent.
+/// No prior state is known if the upstream cannot be probed, such as when replaying.
+async fn prior_accounts(
+    tx: &Transaction,
+    data: &web::Data<GlobalState>,
+) -> BTreeMap<String, AccountOverride> {
+    let programs = tx.program_ids();
+    let keys: Vec<&str> = tx
+        .account_keys
+        .iter()
+        .map(String::as_str)
+        .filter(|k| !programs.contains(k))
+        .collect();
+
+    let body = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "getMultipleAccounts",
+        "params": [keys, {"encoding": "base64", "commitment": "processed"}],
+    });
+    let req = match RpcRequest::parse(0, None, body.to_string().into()) {
+        Ok(r) => r,
+        Err(_) => return BTreeMap::new(),
+    };
+
+    let accounts = match data.upstream.probe(&req).await {
+        Ok(Some((_, body))) => serde_json::from_slice::<Value>(&body).unwrap_or_default(),
+        _ => return BTreeMap::new(),
+    };
+    match accounts.pointer("/result/value") {
+        Some(Value::Array(values)) => keys
+            .iter()
+            .zip(values)
+            .map(|(key, acc)| (key.to_string(), account::capture(acc)))
+            .collect(),
+        _ => BTreeMap::new(),
+    }
+}
+
+/// Caps the commitment of the argued signature status at the level that it is
+/// allowed to have reached after being seen for the elapsed duration.
+fn hold_commitment(status: &mut Value, elapsed: Duration, settings: &CommitmentSettings) {
+    let processed = Duration::from_millis(settings.processed_ms);
+    let confirmed = processed + Duration::from_millis(settings.confirmed_ms);
+
+    let (held, confirmations) = if elapsed < processed {
+        ("processed", 0)
+    } else if elapsed < confirmed {
+        let slots = (elapsed - processed).as_millis() / SLOT_DURATION.as_millis();
+        ("confirmed", (slots as u64).clamp(1, 31))
+    } else {
+        return;
+    };
+
+    let rank = |level: Option<&str>| match level {
+        Some("processed") => 0,
+        Some("confirmed") => 1,
+        _ => 2,
+    };
+
+    if rank(status.get("confirmationStatus").and_then(Value::as_str)) > rank(Some(held)) {
+        status["confirmationStatus"] = json!(held);
+        status["confirmations"] = json!(confirmations);
+    }
+}
+
+/// Renders the default error page of an nginx reverse proxy for the argued status line.
+fn nginx_error_page(title: &str) -> String {
+    format!(
+        "<html>\r\n<head><title>{0}</title></head>\r\n<body>\r\n<center><h1>{0}</h1></center>\r\n<hr><center>nginx</center>\r\n</body>\r\n</html>\r\n",
+        title
+    )
+}
+
 /// Creates a randomly generated 64-byte and base-58 encoded signature string
 /// to be used for mocking transaction confirmation errors during intercepted RPC calls.
 ///
@@ -168,12 +1271,16 @@ fn generate_fake_signature<R: Rng + ?Sized>(r: &mut R) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::{Config, DuplicateSettings, FaultRule, FaultSettings, PercentageSettings};
+    use crate::event_log::EventFilter;
+    use crate::transaction::tests::wire_transaction;
+    use crate::ServerBuilder;
 
     #[actix_rt::test]
     async fn event_responses() {
         let rate_limit_res = RpcEvent::RateLimit
             .respond(
-                &web::Bytes::default(),
+                &RpcRequest::default(),
                 &web::Data::new(GlobalState::default()),
             )
             .await
@@ -190,7 +1297,7 @@ mod tests {
 
         let timeout_res = RpcEvent::Timeout
             .respond(
-                &web::Bytes::default(),
+                &RpcRequest::default(),
                 &web::Data::new(GlobalState::default()),
             )
             .await
@@ -227,4 +1334,303 @@ mod tests {
 
         assert_ne!(events1, events2);
     }
+
+    #[actix_rt::test]
+    async fn malformed_responses() {
+        let data = web::Data::new(GlobalState::default());
+
+        let invalid_res = RpcEvent::InvalidJson
+            .respond(&RpcRequest::default(), &data)
+            .await
+            .unwrap();
+        let invalid_body = actix_web::body::to_bytes(invalid_res.into_body())
+            .await
+            .unwrap();
+        assert!(serde_json::from_slice::<Value>(&invalid_body).is_err());
+
+        let empty_res = RpcEvent::EmptyResponse
+            .respond(&RpcRequest::default(), &data)
+            .await
+            .unwrap();
+        assert!(empty_res.status().is_success());
+        assert!(actix_web::body::to_bytes(empty_res.into_body())
+            .await
+            .unwrap()
+            .is_empty());
+
+        let html_res = RpcEvent::HtmlError
+            .respond(&RpcRequest::default(), &data)
+            .await
+            .unwrap();
+        assert!(html_res.status().is_server_error());
+        assert_eq!(
+            html_res.headers().get("Content-Type"),
+            Some(&HeaderValue::from_str("text/html").unwrap())
+        );
+    }
+
+    #[tokio::test]
+    async fn broken_transport() {
+        let server = ServerBuilder::new(Config {
+            mock_upstream: true,
+            ..Default::default()
+        })
+        .port(0)
+        .workers(1)
+        .start()
+        .unwrap();
+
+        server.set_percentages(PercentageSettings::all_success());
+        server
+            .set_faults(FaultSettings {
+                weights: Default::default(),
+                rules: vec![
+                    FaultRule::on_method("ConnectionReset", "getHealth"),
+                    FaultRule::on_method("TruncatedBody", "getSlot"),
+                    FaultRule::on_method("WrongContentLength", "getLatestBlockhash"),
+                ],
+            })
+            .unwrap();
+
+        for method in ["getHealth", "getSlot", "getLatestBlockhash"] {
+            let body = reqwest::Client::new()
+                .post(server.url())
+                .header("Content-Type", "application/json")
+                .body(json!({"jsonrpc": "2.0", "id": 1, "method": method}).to_string())
+                .send()
+                .await;
+
+            let parsed = match body {
+                Ok(res) => match res.bytes().await {
+                    Ok(bytes) => serde_json::from_slice::<Value>(&bytes).is_ok(),
+                    Err(_) => false,
+                },
+                Err(_) => false,
+            };
+
+            assert!(!parsed, "{} returned a complete response", method);
+        }
+    }
+
+    #[tokio::test]
+    async fn hung_connections() {
+        let server = ServerBuilder::new(Config {
+            mock_upstream: true,
+            ..Default::default()
+        })
+        .port(0)
+        .workers(1)
+        .start()
+        .unwrap();
+
+        server.set_percentages(PercentageSettings::all_success());
+        server
+            .set_faults(FaultSettings {
+                weights: Default::default(),
+                rules: vec![
+                    FaultRule::on_method("Hang", "getHealth"),
+                    FaultRule::on_method("StallBody", "getSlot"),
+                ],
+            })
+            .unwrap();
+
+        let client = reqwest::Client::new();
+        let request = |method: &str| {
+            client
+                .post(server.url())
+                .header("Content-Type", "application/json")
+                .body(json!({"jsonrpc": "2.0", "id": 1, "method": method}).to_string())
+                .send()
+        };
+        let wait = Duration::from_millis(500);
+
+        assert!(tokio::time::timeout(wait, request("getHealth"))
+            .await
+            .is_err());
+        assert_eq!(
+            server.events(&Default::default())[0].event,
+            Some("Hang".into())
+        );
+
+        let stalled = tokio::time::timeout(wait, request("getSlot"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stalled.headers().get("X-ATC-Event").unwrap(), "StallBody");
+        assert!(tokio::time::timeout(wait, stalled.bytes()).await.is_err());
+    }
+
+    #[test]
+    fn throttled_chunks() {
+        let body = web::Bytes::from(vec![7u8; 2500]);
+        let settings = ThrottleSettings {
+            bytes_per_second: 1000,
+            chunk_size: 1000,
+            jitter: 0.0,
+        };
+
+        let chunks = throttle_schedule(body.clone(), &settings, &mut thread_rng());
+        assert_eq!(
+            chunks.iter().map(|(c, _)| c.len()).collect::<Vec<_>>(),
+            vec![1000, 1000, 500]
+        );
+        assert_eq!(
+            chunks.iter().map(|(_, d)| *d).sum::<Duration>(),
+            Duration::from_millis(2500)
+        );
+
+        let jittered = throttle_schedule(
+            body,
+            &ThrottleSettings {
+                jitter: 0.5,
+                ..settings
+            },
+            &mut thread_rng(),
+        );
+        for (_, d) in jittered.iter().take(2) {
+            assert!(*d >= Duration::from_millis(500) && *d <= Duration::from_millis(1500));
+        }
+    }
+
+    #[tokio::test]
+    async fn throttled_passthrough() {
+        let server = ServerBuilder::new(Config {
+            mock_upstream: true,
+            percentages: PercentageSettings::all_success(),
+            faults: FaultSettings {
+                weights: Default::default(),
+                rules: vec![FaultRule {
+                    fault: "Throttle".into(),
+                    methods: Vec::new(),
+                    rate: 1.0,
+                    ..Default::default()
+                }],
+            },
+            throttle: ThrottleSettings {
+                bytes_per_second: 200,
+                chunk_size: 20,
+                jitter: 0.2,
+            },
+            ..Default::default()
+        })
+        .port(0)
+        .workers(1)
+        .start()
+        .unwrap();
+
+        let started = std::time::Instant::now();
+        let res = reqwest::Client::new()
+            .post(server.url())
+            .header("Content-Type", "application/json")
+            .body(r#"{"jsonrpc":"2.0","id":1,"method":"getLatestBlockhash"}"#)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.headers().get("X-ATC-Event").unwrap(), "Throttle");
+
+        let body = res.bytes().await.unwrap();
+        let parsed: Value = serde_json::from_slice(&body).unwrap();
+        assert!(parsed["result"]["value"]["blockhash"].is_string());
+
+        // Every 200 bytes of the body take at least 0.8 seconds with the jitter applied.
+        let min = Duration::from_secs_f64(body.len() as f64 / 200.0 * 0.8);
+        assert!(started.elapsed() >= min);
+        let event = &server.events(&Default::default())[0];
+        assert!(event.injected_delay_ms > 0);
+        assert!(event.duration_ms >= event.injected_delay_ms);
+    }
+
+    #[tokio::test]
+    async fn duplicate_and_lost_submissions() {
+        let server = ServerBuilder::new(Config {
+            mock_upstream: true,
+            percentages: PercentageSettings::all_success(),
+            faults: FaultSettings {
+                weights: Default::default(),
+                rules: vec![
+                    FaultRule::on_fee_payer("DuplicateTransaction", [1; 32]),
+                    FaultRule::on_fee_payer("LostResponse", [2; 32]),
+                ],
+            },
+            duplicates: DuplicateSettings {
+                count: 3,
+                spacing_ms: 50,
+            },
+            ..Default::default()
+        })
+        .port(0)
+        .workers(1)
+        .start()
+        .unwrap();
+
+        let post = |body: Value| {
+            reqwest::Client::new()
+                .post(server.url())
+                .header("Content-Type", "application/json")
+                .body(body.to_string())
+                .send()
+        };
+        let send = |payer: [u8; 32]| {
+            let tx = bs58::encode(wire_transaction([payer[0]; 64], &[payer])).into_string();
+            post(json!({"jsonrpc": "2.0", "id": 1, "method": "sendTransaction", "params": [tx]}))
+        };
+
+        let dup: Value =
+            serde_json::from_slice(&send([1; 32]).await.unwrap().bytes().await.unwrap()).unwrap();
+        assert_eq!(dup["result"], bs58::encode([1; 64]).into_string());
+
+        let lost = match send([2; 32]).await {
+            Ok(res) => res.bytes().await.is_err(),
+            Err(_) => true,
+        };
+        assert!(lost);
+
+        let lost_sig = bs58::encode([2; 64]).into_string();
+        let statuses: Value = serde_json::from_slice(
+            &post(json!({"jsonrpc": "2.0", "id": 2, "method": "getSignatureStatuses", "params": [[lost_sig]]}))
+                .await
+                .unwrap()
+                .bytes()
+                .await
+                .unwrap(),
+        )
+        .unwrap();
+        assert!(statuses["result"]["value"][0]["confirmationStatus"].is_string());
+
+        tokio::time::sleep(Duration::from_millis(300)).await;
+
+        let copies = server.events(&EventFilter {
+            event: Some("DuplicateTransaction".into()),
+            ..Default::default()
+        });
+        assert_eq!(copies.len(), 3);
+        assert!(copies[1..].iter().all(|e| e.upstream_status == Some(200)));
+    }
+
+    #[test]
+    fn held_commitment() {
+        let settings = CommitmentSettings {
+            processed_ms: 1_000,
+            confirmed_ms: 10_000,
+        };
+        let finalized = json!({"slot": 5, "confirmations": null, "err": null, "confirmationStatus": "finalized"});
+
+        let mut early = finalized.clone();
+        hold_commitment(&mut early, Duration::from_millis(500), &settings);
+        assert_eq!(early["confirmationStatus"], "processed");
+        assert_eq!(early["confirmations"], 0);
+
+        let mut middle = finalized.clone();
+        hold_commitment(&mut middle, Duration::from_millis(3_000), &settings);
+        assert_eq!(middle["confirmationStatus"], "confirmed");
+        assert_eq!(middle["confirmations"], 5);
+
+        let mut late = finalized.clone();
+        hold_commitment(&mut late, Duration::from_millis(11_000), &settings);
+        assert_eq!(late, finalized);
+
+        let mut behind = json!({"confirmations": 0, "confirmationStatus": "processed"});
+        hold_commitment(&mut behind, Duration::from_millis(3_000), &settings);
+        assert_eq!(behind["confirmationStatus"], "processed");
+    }
 }
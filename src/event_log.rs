@@ -0,0 +1,336 @@
+// Copyright (C) 2022 JET PROTOCOL HOLDINGS, LLC.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::{EventLogSettings, LogVerbosity};
+use crate::request::{Reason, RpcRequest};
+
+/// The maximum number of injected event records kept in memory
+/// for querying before the oldest records are discarded.
+const HISTORY_CAPACITY: usize = 10_000;
+
+/// A single structured log entry describing how the
+/// proxy handled an incoming RPC request.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventRecord {
+    pub request_id: u64,
+    pub timestamp: u64,
+    pub method: String,
+    pub client: Option<String>,
+    pub event: Option<String>,
+    pub reason: Reason,
+    pub injected_delay_ms: u64,
+    pub upstream_status: Option<u16>,
+    pub duration_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub params: Option<Value>,
+}
+
+impl EventRecord {
+    /// Creates a new record from the final trace of the argued request
+    /// and the error that it resulted in, if there was one.
+    pub fn new(req: &RpcRequest, error: Option<String>) -> Self {
+        let trace = req.trace();
+        Self {
+            request_id: req.id,
+            timestamp: unix_millis(),
+            method: req.method.clone(),
+            client: req.client.map(|c| c.to_string()),
+            event: trace.event,
+            reason: trace.reason,
+            injected_delay_ms: trace.injected_delay.as_millis() as u64,
+            upstream_status: trace.upstream_status,
+            duration_ms: (req.started.elapsed() + trace.streaming).as_millis() as u64,
+            signature: trace.signature,
+            error,
+            params: None,
+        }
+    }
+}
+
+/// Query filters for reading the injected event history,
+/// where each provided field must match for a record to be returned.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventFilter {
+    pub method: Option<String>,
+    pub event: Option<String>,
+    pub since: Option<u64>,
+}
+
+impl EventFilter {
+    /// Returns whether the argued record satisfies all filter fields.
+    pub fn matches(&self, rec: &EventRecord) -> bool {
+        if matches!(&self.method, Some(m) if *m != rec.method) {
+            return false;
+        }
+
+        if matches!(&self.event, Some(e) if rec.event.as_ref() != Some(e)) {
+            return false;
+        }
+
+        !matches!(self.since, Some(t) if rec.timestamp < t)
+    }
+}
+
+/// Writer of JSON lines for each request handled by the proxy
+/// that is filtered by the configured log verbosity, which also
+/// keeps the recent history of injected events for querying.
+pub struct EventLog {
+    history: RwLock<VecDeque<EventRecord>>,
+    next_id: AtomicU64,
+    verbosity: LogVerbosity,
+    writer: Mutex<Box<dyn Write + Send>>,
+}
+
+impl EventLog {
+    /// Creates a new `EventLog` that writes to the argued output.
+    pub fn new(verbosity: LogVerbosity, writer: Box<dyn Write + Send>) -> Self {
+        Self {
+            history: RwLock::new(VecDeque::new()),
+            next_id: AtomicU64::new(1),
+            verbosity,
+            writer: Mutex::new(writer),
+        }
+    }
+
+    /// Returns a new unique identifier for an incoming request.
+    pub fn next_id(&self) -> u64 {
+        self.next_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Returns the recorded injected events that match the argued filter
+    /// in the order that they occurred.
+    pub fn history(&self, filter: &EventFilter) -> Vec<EventRecord> {
+        self.history
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|r| filter.matches(r))
+            .cloned()
+            .collect()
+    }
+
+    /// Discards all of the recorded injected event history.
+    pub fn clear_history(&self) {
+        self.history.write().unwrap().clear();
+    }
+
+    /// Writes the record for the argued request to the log output
+    /// if it is allowed by the configured verbosity level and adds it
+    /// to the event history if an event was injected.
+    pub fn record(&self, req: &RpcRequest, error: Option<String>) {
+        let mut rec = EventRecord::new(req, error);
+
+        if rec.event.is_some() {
+            let mut history = self.history.write().unwrap();
+            if history.len() >= HISTORY_CAPACITY {
+                history.pop_front();
+            }
+            history.push_back(rec.clone());
+        }
+
+        let level = if rec.event.is_some() || rec.error.is_some() {
+            LogVerbosity::Faults
+        } else {
+            LogVerbosity::All
+        };
+
+        if self.verbosity < level {
+            return;
+        }
+
+        if self.verbosity >= LogVerbosity::Debug {
+            rec.params = req.body.get("params").cloned();
+        }
+
+        if let Ok(line) = serde_json::to_string(&rec) {
+            let mut w = self.writer.lock().unwrap();
+            let _ = writeln!(w, "{}", line).and_then(|_| w.flush());
+        }
+    }
+}
+
+impl Default for EventLog {
+    fn default() -> Self {
+        Self::new(LogVerbosity::Off, Box::new(std::io::sink()))
+    }
+}
+
+impl TryFrom<&EventLogSettings> for EventLog {
+    type Error = std::io::Error;
+
+    fn try_from(s: &EventLogSettings) -> Result<Self, Self::Error> {
+        let writer: Box<dyn Write + Send> = match s.output.as_str() {
+            "stdout" => Box::new(std::io::stdout()),
+            "stderr" => Box::new(std::io::stderr()),
+            path => Box::new(OpenOptions::new().create(true).append(true).open(path)?),
+        };
+        Ok(Self::new(s.verbosity, writer))
+    }
+}
+
+/// Returns the current system time as milliseconds since the Unix epoch.
+pub fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use serde_json::json;
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl SharedBuf {
+        fn lines(&self) -> Vec<Value> {
+            String::from_utf8(self.0.lock().unwrap().clone())
+                .unwrap()
+                .lines()
+                .map(|l| serde_json::from_str(l).unwrap())
+                .collect()
+        }
+    }
+
+    fn request(method: &str) -> RpcRequest {
+        let payload = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": method,
+            "params": [1]
+        })
+        .to_string();
+        RpcRequest::parse(1, Some("127.0.0.1:4000".parse().unwrap()), payload.into()).unwrap()
+    }
+
+    #[test]
+    fn verbosity_filtering() {
+        let buf = SharedBuf::default();
+        let log = EventLog::new(LogVerbosity::Faults, Box::new(buf.clone()));
+
+        log.record(&request("getSlot"), None);
+
+        let faulted = request("getBalance");
+        faulted.decide(
+            "RateLimit",
+            Reason::RpcSuccessRate {
+                draw: 0.7,
+                threshold: 0.65,
+            },
+        );
+        log.record(&faulted, None);
+
+        let lines = buf.lines();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0]["method"], "getBalance");
+        assert_eq!(lines[0]["event"], "RateLimit");
+        assert_eq!(lines[0]["client"], "127.0.0.1:4000");
+        assert_eq!(lines[0]["reason"]["kind"], "rpcSuccessRate");
+        assert_eq!(lines[0]["reason"]["threshold"], json!(0.65));
+        assert!(lines[0].get("params").is_none());
+    }
+
+    #[test]
+    fn debug_includes_params() {
+        let buf = SharedBuf::default();
+        let log = EventLog::new(LogVerbosity::Debug, Box::new(buf.clone()));
+
+        log.record(&request("getSlot"), None);
+
+        let lines = buf.lines();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0]["event"], Value::Null);
+        assert_eq!(lines[0]["reason"]["kind"], "passthrough");
+        assert_eq!(lines[0]["params"], json!([1]));
+    }
+
+    #[test]
+    fn request_ids_increment() {
+        let log = EventLog::default();
+        assert_eq!(log.next_id(), 1);
+        assert_eq!(log.next_id(), 2);
+    }
+
+    #[test]
+    fn history_filters() {
+        let log = EventLog::default();
+
+        log.record(&request("getSlot"), None);
+
+        let limited = request("getSlot");
+        limited.decide("RateLimit", Reason::FakeSignature);
+        log.record(&limited, None);
+
+        let falsified = request("sendTransaction");
+        falsified.decide("FalsifiedSignature", Reason::FakeSignature);
+        falsified.set_signature("abc");
+        log.record(&falsified, None);
+
+        assert_eq!(log.history(&EventFilter::default()).len(), 2);
+
+        let by_method = log.history(&EventFilter {
+            method: Some("sendTransaction".into()),
+            ..Default::default()
+        });
+        assert_eq!(by_method.len(), 1);
+        assert_eq!(by_method[0].signature.as_deref(), Some("abc"));
+
+        let by_event = log.history(&EventFilter {
+            event: Some("RateLimit".into()),
+            ..Default::default()
+        });
+        assert_eq!(by_event.len(), 1);
+        assert_eq!(by_event[0].method, "getSlot");
+
+        let future = log.history(&EventFilter {
+            since: Some(unix_millis() + 60_000),
+            ..Default::default()
+        });
+        assert!(future.is_empty());
+
+        log.clear_history();
+        assert!(log.history(&EventFilter::default()).is_empty());
+    }
+}
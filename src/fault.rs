@@ -0,0 +1,326 @@
+// Copyright (C) 2022 JET PROTOCOL HOLDINGS, LLC.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use actix_web::{web, HttpResponse};
+use async_trait::async_trait;
+use rand::Rng;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, RwLock};
+
+use crate::config::{FaultRule, FaultSettings};
+use crate::event::RpcEvent;
+use crate::request::RpcRequest;
+use crate::service::GlobalState;
+
+/// Behaviour of a fault that can be injected into an RPC request.
+///
+/// Implementations can inspect the incoming request, optionally forward it to
+/// the upstream with `service::passthrough` or `GlobalState::upstream`, and then
+/// change or entirely replace the response that is returned to the client.
+///
+/// # Example
+///
+/// ```
+/// use actix_web::{web, HttpResponse};
+/// use async_trait::async_trait;
+/// use atc::fault::Fault;
+/// use atc::request::RpcRequest;
+/// use atc::GlobalState;
+///
+/// struct Unavailable;
+///
+/// #[async_trait(?Send)]
+/// impl Fault for Unavailable {
+///     fn name(&self) -> &str {
+///         "Unavailable"
+///     }
+///
+///     async fn respond(
+///         &self,
+///         _req: &RpcRequest,
+///         _data: &web::Data<GlobalState>,
+///     ) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+///         Ok(HttpResponse::ServiceUnavailable().finish())
+///     }
+/// }
+///
+/// let state = GlobalState::default();
+/// state.faults.register(Unavailable);
+/// assert!(state.faults.get("Unavailable").is_some());
+/// ```
+#[async_trait(?Send)]
+pub trait Fault: Send + Sync {
+    /// The unique name of the fault that configuration refers to it by.
+    fn name(&self) -> &str;
+
+    /// Returns whether the fault can be injected into the argued request, where
+    /// requests that a fault declines are handled as if it was never chosen.
+    fn applies(&self, _req: &RpcRequest, _data: &GlobalState) -> bool {
+        true
+    }
+
+    /// Produces the response for the intercepted request.
+    async fn respond(
+        &self,
+        req: &RpcRequest,
+        data: &web::Data<GlobalState>,
+    ) -> Result<HttpResponse, Box<dyn std::error::Error>>;
+}
+
+/// Named collection of the faults that are available for injection,
+/// which always includes each of the built-in `RpcEvent` variants.
+pub struct FaultRegistry {
+    faults: RwLock<HashMap<String, Arc<dyn Fault>>>,
+}
+
+impl FaultRegistry {
+    /// Adds the argued fault to the registry, replacing any
+    /// previously registered fault with the same name.
+    pub fn register(&self, fault: impl Fault + 'static) {
+        self.register_arc(Arc::new(fault));
+    }
+
+    /// Adds the argued shared fault to the registry.
+    pub fn register_arc(&self, fault: Arc<dyn Fault>) {
+        self.faults
+            .write()
+            .unwrap()
+            .insert(fault.name().to_string(), fault);
+    }
+
+    /// Returns the fault registered under the argued name.
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Fault>> {
+        self.faults.read().unwrap().get(name).cloned()
+    }
+
+    /// Returns the registered fault with the argued name or an error
+    /// that the configuration refers to an unknown fault.
+    pub fn lookup(&self, name: &str) -> Result<Arc<dyn Fault>, Box<dyn std::error::Error>> {
+        self.get(name)
+            .ok_or_else(|| format!("no fault is registered with the name {}", name).into())
+    }
+
+    /// Returns an error if the argued rules or weights refer to a fault that is not registered,
+    /// so that misconfigured faults are caught before any request is handled.
+    pub fn validate(&self, settings: &FaultSettings) -> std::io::Result<()> {
+        let names = settings
+            .rules
+            .iter()
+            .map(|r| &r.fault)
+            .chain(settings.weights.keys());
+
+        for name in names {
+            if self.get(name).is_none() {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("no fault is registered with the name {}", name),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the names of all registered faults in sorted order.
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.faults.read().unwrap().keys().cloned().collect();
+        names.sort();
+        names
+    }
+}
+
+impl Default for FaultRegistry {
+    fn default() -> Self {
+        let registry = Self {
+            faults: RwLock::new(HashMap::new()),
+        };
+        for event in RpcEvent::all() {
+            registry.register(event);
+        }
+        registry
+    }
+}
+
+/// Selects a fault name from the argued weights with a probability
+/// proportional to its weight, or `None` if all weights are zero.
+pub fn choose_weighted<R: Rng + ?Sized>(
+    weights: &BTreeMap<String, u32>,
+    rng: &mut R,
+) -> Option<String> {
+    let total: u32 = weights.values().sum();
+    if total == 0 {
+        return None;
+    }
+
+    let mut pick = rng.gen_range(0..total);
+    for (name, &w) in weights {
+        if pick < w {
+            return Some(name.clone());
+        }
+        pick -= w;
+    }
+    None
+}
+
+impl FaultRule {
+    /// Returns whether the rule applies to the argued request, where rules
+    /// with program or fee payer filters only apply to decodable transactions.
+    pub fn matches(&self, req: &RpcRequest) -> bool {
+        if !self.methods.is_empty() && !self.methods.contains(&req.method) {
+            return false;
+        }
+
+        if self.programs.is_empty() && self.fee_payers.is_empty() {
+            return true;
+        }
+
+        match &req.transaction {
+            Some(tx) => {
+                (self.programs.is_empty() || self.programs.iter().any(|p| tx.invokes(p)))
+                    && (self.fee_payers.is_empty()
+                        || self.fee_payers.iter().any(|f| f == tx.fee_payer()))
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::StatusCode;
+    use rand::thread_rng;
+    use serde_json::json;
+
+    use super::*;
+    use crate::transaction::tests::wire_transaction_with;
+
+    struct Teapot;
+
+    #[async_trait(?Send)]
+    impl Fault for Teapot {
+        fn name(&self) -> &str {
+            "Teapot"
+        }
+
+        async fn respond(
+            &self,
+            _req: &RpcRequest,
+            _data: &web::Data<GlobalState>,
+        ) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+            Ok(HttpResponse::build(StatusCode::IM_A_TEAPOT).finish())
+        }
+    }
+
+    #[test]
+    fn registry_builtins_and_custom() {
+        let registry = FaultRegistry::default();
+        assert!(registry.get("RateLimit").is_some());
+        assert!(registry.get("Teapot").is_none());
+        assert!(registry.lookup("Teapot").is_err());
+
+        let settings = FaultSettings {
+            weights: [("Teapot".to_string(), 1)].into_iter().collect(),
+            rules: Vec::new(),
+        };
+        assert!(registry.validate(&settings).is_err());
+        assert!(registry.validate(&FaultSettings::default()).is_ok());
+
+        registry.register(Teapot);
+        assert!(registry.validate(&settings).is_ok());
+        assert_eq!(registry.get("Teapot").unwrap().name(), "Teapot");
+        assert!(registry.names().contains(&"Teapot".to_string()));
+    }
+
+    #[test]
+    fn weighted_choice() {
+        let mut rng = thread_rng();
+
+        let only: BTreeMap<String, u32> = [("RateLimit".to_string(), 3), ("Timeout".into(), 0)]
+            .into_iter()
+            .collect();
+        for _ in 0..20 {
+            assert_eq!(
+                choose_weighted(&only, &mut rng).as_deref(),
+                Some("RateLimit")
+            );
+        }
+
+        let none: BTreeMap<String, u32> = [("RateLimit".to_string(), 0)].into_iter().collect();
+        assert_eq!(choose_weighted(&none, &mut rng), None);
+    }
+
+    #[test]
+    fn rule_matching() {
+        let req = RpcRequest::parse(
+            1,
+            None,
+            r#"{"jsonrpc":"2.0","id":1,"method":"getSlot"}"#.into(),
+        )
+        .unwrap();
+
+        let any = FaultRule {
+            fault: "RateLimit".into(),
+            methods: Vec::new(),
+            rate: 1.0,
+            ..Default::default()
+        };
+        let other = FaultRule {
+            methods: vec!["getBalance".into()],
+            ..any.clone()
+        };
+
+        assert!(any.matches(&req));
+        assert!(!other.matches(&req));
+    }
+
+    #[test]
+    fn transaction_rule_matching() {
+        let program = [5; 32];
+        let tx = wire_transaction_with([1; 64], &[[2; 32], program], &[(1, &[0])]);
+        let req = RpcRequest::parse(
+            1,
+            None,
+            json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "sendTransaction",
+                "params": [bs58::encode(tx).into_string()]
+            })
+            .to_string()
+            .into(),
+        )
+        .unwrap();
+
+        let by_program = FaultRule {
+            fault: "RateLimit".into(),
+            programs: vec![bs58::encode(program).into_string()],
+            rate: 1.0,
+            ..Default::default()
+        };
+        let by_payer = FaultRule {
+            programs: Vec::new(),
+            fee_payers: vec![bs58::encode([2; 32]).into_string()],
+            ..by_program.clone()
+        };
+        let other_payer = FaultRule {
+            fee_payers: vec![bs58::encode([3; 32]).into_string()],
+            ..by_program.clone()
+        };
+
+        assert!(by_program.matches(&req));
+        assert!(by_payer.matches(&req));
+        assert!(!other_payer.matches(&req));
+        assert!(!by_program.matches(&RpcRequest::default()));
+    }
+}
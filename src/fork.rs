@@ -0,0 +1,152 @@
+// Copyright (C) 2022 JET PROTOCOL HOLDINGS, LLC.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+use crate::config::{AccountOverride, ForkSettings};
+
+/// A transaction that landed on a fork which is later abandoned,
+/// with the state of its accounts from before it landed.
+#[derive(Clone, Debug)]
+struct ForkedTransaction {
+    landed_at: Instant,
+    slot: u64,
+    previous: BTreeMap<String, AccountOverride>,
+}
+
+/// Tracks the transactions that landed on simulated forks which are later
+/// abandoned, so that they and their account changes are rolled back
+/// consistently until they land again on the surviving fork.
+#[derive(Debug, Default)]
+pub struct ForkTracker {
+    transactions: RwLock<HashMap<String, ForkedTransaction>>,
+}
+
+impl ForkTracker {
+    /// Records that the argued signature landed in the argued slot of a fork that
+    /// will be abandoned, and the state of its accounts from before it landed.
+    pub fn abandon(&self, sig: &str, slot: u64, previous: BTreeMap<String, AccountOverride>) {
+        self.transactions
+            .write()
+            .unwrap()
+            .entry(sig.to_string())
+            .or_insert(ForkedTransaction {
+                landed_at: Instant::now(),
+                slot,
+                previous,
+            });
+    }
+
+    /// Returns whether the argued signature is on a fork, either before or after it
+    /// is abandoned, and has not landed again on the surviving fork yet.
+    pub fn is_forked(&self, sig: &str, settings: &ForkSettings) -> bool {
+        self.prune(settings);
+        self.transactions.read().unwrap().contains_key(sig)
+    }
+
+    /// Returns the status reported for the argued signature while it is on a fork,
+    /// which is `processed` until the fork is abandoned and null afterwards, or
+    /// `None` if it is not on a fork and the upstream status is reported.
+    pub fn status(&self, sig: &str, settings: &ForkSettings) -> Option<Value> {
+        self.prune(settings);
+        let rollback = Duration::from_millis(settings.rollback_ms);
+        self.transactions.read().unwrap().get(sig).map(|tx| {
+            if tx.landed_at.elapsed() >= rollback {
+                return Value::Null;
+            }
+            json!({
+                "slot": tx.slot,
+                "confirmations": 0,
+                "err": null,
+                "status": {"Ok": null},
+                "confirmationStatus": "processed",
+            })
+        })
+    }
+
+    /// Returns the previous state of the accounts changed by transactions on
+    /// abandoned forks, which is reported until they land on the surviving fork.
+    pub fn reverted(&self, settings: &ForkSettings) -> BTreeMap<String, AccountOverride> {
+        self.prune(settings);
+        let rollback = Duration::from_millis(settings.rollback_ms);
+
+        let txs = self.transactions.read().unwrap();
+        let mut abandoned: Vec<&ForkedTransaction> = txs
+            .values()
+            .filter(|tx| tx.landed_at.elapsed() >= rollback)
+            .collect();
+        abandoned.sort_by_key(|tx| tx.landed_at);
+
+        // The earliest abandoned transaction has the state from before any of them landed.
+        let mut reverted = BTreeMap::new();
+        for tx in abandoned {
+            for (key, previous) in &tx.previous {
+                reverted
+                    .entry(key.clone())
+                    .or_insert_with(|| previous.clone());
+            }
+        }
+        reverted
+    }
+
+    /// Forgets the transactions that have landed again on the surviving fork.
+    fn prune(&self, settings: &ForkSettings) {
+        let rollback = Duration::from_millis(settings.rollback_ms);
+        self.transactions
+            .write()
+            .unwrap()
+            .retain(|_, tx| tx.landed_at.elapsed() < rollback * 2);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn abandoned_transactions() {
+        let forks = ForkTracker::default();
+        let settings = ForkSettings {
+            rollback_rate: 1.0,
+            rollback_ms: 50,
+        };
+        let previous = BTreeMap::from([(
+            "payer".to_string(),
+            AccountOverride {
+                lamports: Some(10),
+                ..Default::default()
+            },
+        )]);
+
+        forks.abandon("sig", 10, previous.clone());
+        let status = forks.status("sig", &settings).unwrap();
+        assert_eq!(status["slot"], 10);
+        assert_eq!(status["confirmationStatus"], "processed");
+        assert_eq!(forks.status("other", &settings), None);
+        assert!(forks.reverted(&settings).is_empty());
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert_eq!(forks.status("sig", &settings), Some(Value::Null));
+        assert_eq!(forks.reverted(&settings), previous);
+
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(!forks.is_forked("sig", &settings));
+        assert_eq!(forks.status("sig", &settings), None);
+        assert!(forks.reverted(&settings).is_empty());
+    }
+}
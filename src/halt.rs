@@ -0,0 +1,132 @@
+// Copyright (C) 2022 JET PROTOCOL HOLDINGS, LLC.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use serde_json::Value;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::mock::SLOT_DURATION;
+
+/// Methods whose results stop advancing while the cluster is halted.
+pub const FROZEN_METHODS: [&str; 4] = [
+    "getBlockHeight",
+    "getEpochInfo",
+    "getLatestBlockhash",
+    "getSlot",
+];
+
+/// The chain view that is reported for the duration of a cluster halt, taken
+/// from a single probe of the upstream so that every frozen method agrees.
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    epoch_info: Value,
+    blockhash: Value,
+}
+
+impl Snapshot {
+    /// Creates a snapshot from the argued `getEpochInfo` and `getLatestBlockhash`
+    /// results, where the blockhash is reported in the context of the frozen slot.
+    pub fn new(epoch_info: Value, mut blockhash: Value) -> Self {
+        if let Some(slot) = blockhash.pointer_mut("/context/slot") {
+            *slot = epoch_info["absoluteSlot"].clone();
+        }
+        Self {
+            epoch_info,
+            blockhash,
+        }
+    }
+
+    /// Returns the frozen result of the argued method, or `None` if it is not frozen.
+    pub fn result(&self, method: &str) -> Option<Value> {
+        match method {
+            "getBlockHeight" => self.epoch_info.get("blockHeight").cloned(),
+            "getEpochInfo" => Some(self.epoch_info.clone()),
+            "getLatestBlockhash" => Some(self.blockhash.clone()),
+            "getSlot" => self.epoch_info.get("absoluteSlot").cloned(),
+            _ => None,
+        }
+    }
+}
+
+/// A simulated cluster halt, which freezes the chain view reported by
+/// the proxy at the first snapshot taken after the halt started.
+#[derive(Debug)]
+pub struct Halt {
+    pub started: Instant,
+    snapshot: Mutex<Option<Snapshot>>,
+}
+
+impl Halt {
+    /// Starts a new cluster halt at the current time.
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            snapshot: Mutex::new(None),
+        }
+    }
+
+    /// Returns the number of slots that the cluster would have produced since it halted.
+    pub fn slots_behind(&self) -> u64 {
+        (self.started.elapsed().as_millis() / SLOT_DURATION.as_millis()) as u64
+    }
+
+    /// Returns the frozen chain view, if a snapshot was taken since the halt started.
+    pub fn snapshot(&self) -> Option<Snapshot> {
+        self.snapshot.lock().unwrap().clone()
+    }
+
+    /// Freezes the chain view at the argued snapshot unless one was already taken,
+    /// returning the snapshot that is reported from now on.
+    pub fn freeze(&self, snapshot: Snapshot) -> Snapshot {
+        self.snapshot
+            .lock()
+            .unwrap()
+            .get_or_insert(snapshot)
+            .clone()
+    }
+}
+
+impl Default for Halt {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn freeze_first_snapshot() {
+        let epoch_info = |slot: u64| json!({"absoluteSlot": slot, "blockHeight": slot - 2});
+        let blockhash = json!({"context": {"slot": 12}, "value": {"blockhash": "a"}});
+
+        let halt = Halt::new();
+        assert!(halt.snapshot().is_none());
+
+        let first = halt.freeze(Snapshot::new(epoch_info(10), blockhash.clone()));
+        let second = halt.freeze(Snapshot::new(epoch_info(11), blockhash));
+        assert_eq!(second.result("getSlot"), Some(json!(10)));
+        assert_eq!(first.result("getBlockHeight"), Some(json!(8)));
+        assert_eq!(
+            halt.snapshot().unwrap().result("getLatestBlockhash"),
+            Some(json!({"context": {"slot": 10}, "value": {"blockhash": "a"}}))
+        );
+        assert_eq!(first.result("getBalance"), None);
+        assert_eq!(halt.slots_behind(), 0);
+    }
+}
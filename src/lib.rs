@@ -0,0 +1,46 @@
+// Copyright (C) 2022 JET PROTOCOL HOLDINGS, LLC.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Solana validator proxy for simulating RPC traffic downtime and errors,
+//! which can be run as the standalone `atc` binary or embedded in tests
+//! through the [`ServerBuilder`].
+
+pub mod account;
+pub mod admin;
+pub mod blockhash;
+pub mod cassette;
+pub mod config;
+pub mod congestion;
+pub mod event;
+pub mod event_log;
+pub mod fault;
+pub mod fork;
+pub mod halt;
+pub mod metrics;
+pub mod mock;
+pub mod mutation;
+pub mod preflight;
+pub mod request;
+pub mod restart;
+pub mod server;
+pub mod service;
+pub mod transaction;
+pub mod upstream;
+pub mod view;
+
+pub use config::Config;
+pub use event::RpcEvent;
+pub use server::{Server, ServerBuilder};
+pub use service::GlobalState;
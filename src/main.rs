@@ -13,14 +13,11 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use actix_web::web;
-use actix_web::{middleware, App, HttpServer};
 use std::path::PathBuf;
 use std::str::FromStr;
 
-mod config;
-mod event;
-mod service;
+use atc::config::Config;
+use atc::ServerBuilder;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
@@ -28,23 +25,14 @@ async fn main() -> std::io::Result<()> {
 
     let config = option_env!("ATC_CONFIG_PATH")
         .map(|path| PathBuf::from_str(path).unwrap())
-        .map(|p| config::Config::try_from(p).unwrap())
+        .map(|p| Config::try_from(p).unwrap())
         .unwrap_or_default();
 
-    println!("{:#?}", config);
+    log::info!("{:?}", config);
 
-    let shared_data = web::Data::new(service::GlobalState::from(config.clone()));
-
-    HttpServer::new(move || {
-        App::new()
-            .wrap(middleware::Compress::default())
-            .wrap(middleware::Logger::default())
-            .app_data(shared_data.clone())
-            .service(service::health)
-            .service(service::rpc)
-    })
-    .bind(("0.0.0.0", config.port))?
-    .workers(config.workers)
-    .run()
-    .await
+    ServerBuilder::new(config)
+        .host("0.0.0.0")
+        .start()?
+        .wait()
+        .await
 }
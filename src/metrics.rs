@@ -0,0 +1,144 @@
+// Copyright (C) 2022 JET PROTOCOL HOLDINGS, LLC.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, Opts, Registry,
+    TextEncoder,
+};
+
+/// Histogram buckets (in seconds) shared by the upstream and injected
+/// latency metrics, covering fast RPC calls up to the injected timeouts.
+const LATENCY_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 20.0, 30.0,
+];
+
+/// Prometheus collectors for the proxied RPC traffic and the
+/// fault events that were injected into it.
+pub struct Metrics {
+    registry: Registry,
+    pub requests: IntCounterVec,
+    pub events: IntCounterVec,
+    pub upstream_responses: IntCounterVec,
+    pub upstream_latency: Histogram,
+    pub injected_latency: HistogramVec,
+    pub fake_signatures: IntGauge,
+}
+
+impl Metrics {
+    /// Encodes all registered collectors in the Prometheus text exposition format.
+    pub fn render(&self) -> Result<String, prometheus::Error> {
+        let mut buf = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buf)?;
+        Ok(String::from_utf8_lossy(&buf).into_owned())
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        let registry = Registry::new_custom(Some("atc".into()), None).unwrap();
+
+        let requests = IntCounterVec::new(
+            Opts::new("requests_total", "Incoming RPC requests by method."),
+            &["method"],
+        )
+        .unwrap();
+
+        let events = IntCounterVec::new(
+            Opts::new("events_total", "Injected RPC events by kind and method."),
+            &["event", "method"],
+        )
+        .unwrap();
+
+        let upstream_responses = IntCounterVec::new(
+            Opts::new(
+                "upstream_responses_total",
+                "Upstream RPC responses by HTTP status code.",
+            ),
+            &["status"],
+        )
+        .unwrap();
+
+        let upstream_latency = Histogram::with_opts(
+            HistogramOpts::new(
+                "upstream_duration_seconds",
+                "Latency of requests passed through to the upstream RPC endpoint.",
+            )
+            .buckets(LATENCY_BUCKETS.to_vec()),
+        )
+        .unwrap();
+
+        let injected_latency = HistogramVec::new(
+            HistogramOpts::new(
+                "injected_delay_seconds",
+                "Artificial delays added to responses by injected events.",
+            )
+            .buckets(LATENCY_BUCKETS.to_vec()),
+            &["event"],
+        )
+        .unwrap();
+
+        let fake_signatures = IntGauge::new(
+            "fake_signatures",
+            "Number of falsified transaction signatures being tracked.",
+        )
+        .unwrap();
+
+        registry.register(Box::new(requests.clone())).unwrap();
+        registry.register(Box::new(events.clone())).unwrap();
+        registry
+            .register(Box::new(upstream_responses.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(upstream_latency.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(injected_latency.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(fake_signatures.clone()))
+            .unwrap();
+
+        Self {
+            registry,
+            requests,
+            events,
+            upstream_responses,
+            upstream_latency,
+            injected_latency,
+            fake_signatures,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_text_format() {
+        let m = Metrics::default();
+        m.requests.with_label_values(&["getSlot"]).inc();
+        m.events
+            .with_label_values(&["RateLimit", "getSlot"])
+            .inc_by(2);
+        m.fake_signatures.set(3);
+
+        let out = m.render().unwrap();
+
+        assert!(out.contains("atc_requests_total{method=\"getSlot\"} 1"));
+        assert!(out.contains("atc_events_total{event=\"RateLimit\",method=\"getSlot\"} 2"));
+        assert!(out.contains("atc_fake_signatures 3"));
+    }
+}
@@ -0,0 +1,383 @@
+// Copyright (C) 2022 JET PROTOCOL HOLDINGS, LLC.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use actix_web::http::StatusCode;
+use actix_web::web;
+use rand::Rng;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::account;
+use crate::request::RpcRequest;
+use crate::transaction::Transaction;
+
+/// The approximate duration of a slot on a Solana cluster.
+pub const SLOT_DURATION: Duration = Duration::from_millis(400);
+
+/// Lamports held by any account that has not yet paid a fee.
+const DEFAULT_BALANCE: u64 = 10_000_000_000;
+
+/// Lamports charged to the fee payer of each sent transaction.
+const SIGNATURE_FEE: u64 = 5_000;
+
+/// Number of slots after which a processed signature is finalized.
+const FINALITY_SLOTS: u64 = 32;
+
+/// Number of blocks that a recent blockhash remains valid for.
+const BLOCKHASH_VALIDITY: u64 = 150;
+
+/// Number of slots in each epoch.
+const SLOTS_PER_EPOCH: u64 = 432_000;
+
+/// Mutable ledger state of the mock validator.
+#[derive(Default)]
+struct Ledger {
+    balances: HashMap<String, u64>,
+    signatures: HashMap<String, u64>,
+}
+
+/// In-process mock of a Solana validator's RPC interface that answers the
+/// common methods from internally consistent state that advances in real time.
+pub struct MockValidator {
+    genesis: Instant,
+    ledger: Mutex<Ledger>,
+}
+
+impl MockValidator {
+    /// Returns the current slot based on the time elapsed since the mock started.
+    pub fn slot(&self) -> u64 {
+        1 + (self.genesis.elapsed().as_millis() / SLOT_DURATION.as_millis()) as u64
+    }
+
+    /// Returns the deterministic blockhash produced for the argued slot.
+    pub fn blockhash(slot: u64) -> String {
+        let mut bytes = [0u8; 32];
+        let mut x = slot;
+        for chunk in bytes.chunks_mut(8) {
+            x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = x;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            chunk.copy_from_slice(&(z ^ (z >> 31)).to_le_bytes());
+        }
+        bs58::encode(bytes).into_string()
+    }
+
+    /// Answers the argued RPC request with a JSON-RPC response body.
+    pub async fn call(
+        &self,
+        req: &RpcRequest,
+    ) -> Result<(StatusCode, web::Bytes), Box<dyn std::error::Error>> {
+        let id = req.id();
+        let body = match self.dispatch(req) {
+            Ok(result) => json!({"jsonrpc": "2.0", "result": result, "id": id}),
+            Err((code, message)) => json!({
+                "jsonrpc": "2.0",
+                "error": {"code": code, "message": message},
+                "id": id,
+            }),
+        };
+        Ok((StatusCode::OK, web::Bytes::from(body.to_string())))
+    }
+
+    /// Computes the result of the RPC method or the JSON-RPC error code and message.
+    fn dispatch(&self, req: &RpcRequest) -> Result<Value, (i64, String)> {
+        let slot = self.slot();
+        let context = json!({"apiVersion": "1.10.24", "slot": slot});
+        let params = req.params();
+
+        match req.method.as_str() {
+            "getHealth" => Ok(json!("ok")),
+            "getSlot" => Ok(json!(slot)),
+            "getBlockHeight" => Ok(json!(slot)),
+            "getEpochInfo" => Ok(json!({
+                "absoluteSlot": slot,
+                "blockHeight": slot,
+                "epoch": slot / SLOTS_PER_EPOCH,
+                "slotIndex": slot % SLOTS_PER_EPOCH,
+                "slotsInEpoch": SLOTS_PER_EPOCH,
+            })),
+            "getLatestBlockhash" => Ok(json!({
+                "context": context,
+                "value": {
+                    "blockhash": Self::blockhash(slot),
+                    "lastValidBlockHeight": slot + BLOCKHASH_VALIDITY,
+                },
+            })),
+            "isBlockhashValid" => {
+                let blockhash = params
+                    .first()
+                    .and_then(Value::as_str)
+                    .ok_or((-32602, "Invalid params: expected blockhash".to_string()))?;
+                let valid = (slot.saturating_sub(BLOCKHASH_VALIDITY)..=slot)
+                    .any(|s| Self::blockhash(s) == blockhash);
+                Ok(json!({"context": context, "value": valid}))
+            }
+            "getBalance" => {
+                let pubkey = pubkey_param(params)?;
+                let lamports = self.balance(&pubkey);
+                Ok(json!({"context": context, "value": lamports}))
+            }
+            "getAccountInfo" => {
+                let pubkey = pubkey_param(params)?;
+                let acc = account::system_account(self.balance(&pubkey), encoding_param(params));
+                Ok(json!({"context": context, "value": acc}))
+            }
+            "getMultipleAccounts" => {
+                let pubkeys = params
+                    .first()
+                    .and_then(Value::as_array)
+                    .ok_or((-32602, "Invalid params: expected pubkeys".to_string()))?;
+                let accounts = pubkeys
+                    .iter()
+                    .map(|p| {
+                        let pubkey = pubkey_param(std::slice::from_ref(p))?;
+                        let lamports = self.balance(&pubkey);
+                        Ok(account::system_account(lamports, encoding_param(params)))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(json!({"context": context, "value": accounts}))
+            }
+            "requestAirdrop" => {
+                let pubkey = pubkey_param(params)?;
+                let lamports = params
+                    .get(1)
+                    .and_then(Value::as_u64)
+                    .ok_or((-32602, "Invalid params: expected lamports".to_string()))?;
+
+                let mut sig = [0u8; 64];
+                rand::thread_rng().fill(&mut sig[..]);
+                let sig = bs58::encode(sig).into_string();
+
+                let mut ledger = self.ledger.lock().unwrap();
+                let balance = ledger.balances.entry(pubkey).or_insert(DEFAULT_BALANCE);
+                *balance = balance.saturating_add(lamports);
+                ledger.signatures.insert(sig.clone(), slot);
+
+                Ok(json!(sig))
+            }
+            "sendTransaction" => {
+                let tx = Transaction::from_params(params).ok_or((
+                    -32602,
+                    "invalid transaction: failed to deserialize transaction".to_string(),
+                ))?;
+
+                let mut ledger = self.ledger.lock().unwrap();
+                let balance = ledger
+                    .balances
+                    .entry(tx.fee_payer().to_string())
+                    .or_insert(DEFAULT_BALANCE);
+                *balance = balance.saturating_sub(SIGNATURE_FEE);
+                ledger
+                    .signatures
+                    .entry(tx.signature().to_string())
+                    .or_insert(slot);
+
+                Ok(json!(tx.signature()))
+            }
+            "getSignatureStatuses" => {
+                let sigs = params
+                    .first()
+                    .and_then(Value::as_array)
+                    .ok_or((-32602, "Invalid params: expected signatures".to_string()))?;
+
+                let ledger = self.ledger.lock().unwrap();
+                let statuses: Vec<Value> = sigs
+                    .iter()
+                    .map(|s| {
+                        s.as_str()
+                            .and_then(|s| ledger.signatures.get(s))
+                            .map_or(Value::Null, |&landed| status(landed, slot))
+                    })
+                    .collect();
+
+                Ok(json!({"context": context, "value": statuses}))
+            }
+            _ => Err((-32601, "Method not found".to_string())),
+        }
+    }
+}
+
+impl MockValidator {
+    /// Returns the lamports held by the system account of the argued public key.
+    fn balance(&self, pubkey: &str) -> u64 {
+        let ledger = self.ledger.lock().unwrap();
+        ledger
+            .balances
+            .get(pubkey)
+            .copied()
+            .unwrap_or(DEFAULT_BALANCE)
+    }
+}
+
+impl Default for MockValidator {
+    fn default() -> Self {
+        Self {
+            genesis: Instant::now(),
+            ledger: Mutex::new(Ledger::default()),
+        }
+    }
+}
+
+/// Builds the signature status object for a transaction that landed
+/// in the argued slot as observed from the current slot.
+fn status(landed: u64, current: u64) -> Value {
+    let confirmations = current.saturating_sub(landed);
+    let (confirmations, level) = match confirmations {
+        0 => (json!(0), "processed"),
+        c if c < FINALITY_SLOTS => (json!(c), "confirmed"),
+        _ => (Value::Null, "finalized"),
+    };
+
+    json!({
+        "slot": landed,
+        "confirmations": confirmations,
+        "err": null,
+        "status": {"Ok": null},
+        "confirmationStatus": level,
+    })
+}
+
+/// Reads and validates the base-58 public key in the first request parameter.
+fn pubkey_param(params: &[Value]) -> Result<String, (i64, String)> {
+    params
+        .first()
+        .and_then(Value::as_str)
+        .filter(|p| matches!(bs58::decode(p).into_vec(), Ok(b) if b.len() == 32))
+        .map(ToString::to_string)
+        .ok_or((-32602, "Invalid param: Invalid".to_string()))
+}
+
+/// Reads the account data encoding from the configuration object in the second parameter.
+fn encoding_param(params: &[Value]) -> Option<&str> {
+    params
+        .get(1)
+        .and_then(|c| c.get("encoding"))
+        .and_then(Value::as_str)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::tests::wire_transaction;
+
+    async fn call(mock: &MockValidator, method: &str, params: Value) -> Value {
+        let payload = json!({
+            "jsonrpc": "2.0",
+            "id": 5,
+            "method": method,
+            "params": params,
+        })
+        .to_string();
+        let req = RpcRequest::parse(1, None, payload.into()).unwrap();
+        let (_, body) = mock.call(&req).await.unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[actix_rt::test]
+    async fn chain_state() {
+        let mock = MockValidator::default();
+
+        assert_eq!(call(&mock, "getHealth", json!([])).await["result"], "ok");
+        assert_eq!(call(&mock, "getSlot", json!([])).await["id"], 5);
+
+        let epoch = call(&mock, "getEpochInfo", json!([])).await;
+        assert_eq!(
+            epoch["result"]["absoluteSlot"],
+            epoch["result"]["blockHeight"]
+        );
+
+        let bh = call(&mock, "getLatestBlockhash", json!([])).await;
+        let slot = bh["result"]["context"]["slot"].as_u64().unwrap();
+        assert_eq!(
+            bh["result"]["value"]["blockhash"],
+            MockValidator::blockhash(slot)
+        );
+        assert_ne!(
+            MockValidator::blockhash(slot),
+            MockValidator::blockhash(slot + 1)
+        );
+
+        let valid = call(
+            &mock,
+            "isBlockhashValid",
+            json!([MockValidator::blockhash(slot)]),
+        )
+        .await;
+        assert_eq!(valid["result"]["value"], true);
+        let invalid = call(&mock, "isBlockhashValid", json!(["unknown"])).await;
+        assert_eq!(invalid["result"]["value"], false);
+
+        let unknown = call(&mock, "getProgramAccounts", json!([])).await;
+        assert_eq!(unknown["error"]["code"], -32601);
+    }
+
+    #[actix_rt::test]
+    async fn send_and_confirm() {
+        let mock = MockValidator::default();
+        let payer = bs58::encode([1; 32]).into_string();
+        let tx = bs58::encode(wire_transaction([8; 64], &[[1; 32]])).into_string();
+
+        let sent = call(&mock, "sendTransaction", json!([tx])).await;
+        let sig = bs58::encode([8; 64]).into_string();
+        assert_eq!(sent["result"], sig);
+
+        let balance = call(&mock, "getBalance", json!([payer])).await;
+        assert_eq!(balance["result"]["value"], DEFAULT_BALANCE - SIGNATURE_FEE);
+        let accounts = call(
+            &mock,
+            "getMultipleAccounts",
+            json!([[payer], {"encoding": "base64"}]),
+        )
+        .await;
+        assert_eq!(
+            accounts["result"]["value"][0]["lamports"],
+            DEFAULT_BALANCE - SIGNATURE_FEE
+        );
+        assert_eq!(
+            accounts["result"]["value"][0]["data"],
+            json!(["", "base64"])
+        );
+
+        let statuses = call(&mock, "getSignatureStatuses", json!([[sig, "unknown"]])).await;
+        let value = &statuses["result"]["value"];
+        assert_eq!(value[0]["err"], Value::Null);
+        assert!(value[0]["confirmationStatus"].is_string());
+        assert_eq!(value[1], Value::Null);
+
+        let invalid = call(&mock, "sendTransaction", json!([""])).await;
+        assert_eq!(invalid["error"]["code"], -32602);
+
+        let airdrop = call(&mock, "requestAirdrop", json!([payer, 1_000])).await;
+        let statuses = call(&mock, "getSignatureStatuses", json!([[airdrop["result"]]])).await;
+        assert!(statuses["result"]["value"][0].is_object());
+        let balance = call(&mock, "getBalance", json!([payer])).await;
+        assert_eq!(
+            balance["result"]["value"],
+            DEFAULT_BALANCE - SIGNATURE_FEE + 1_000
+        );
+    }
+
+    #[test]
+    fn status_progression() {
+        assert_eq!(status(10, 10)["confirmationStatus"], "processed");
+        assert_eq!(status(10, 12)["confirmationStatus"], "confirmed");
+        assert_eq!(status(10, 12)["confirmations"], 2);
+        assert_eq!(status(10, 50)["confirmationStatus"], "finalized");
+        assert_eq!(status(10, 50)["confirmations"], Value::Null);
+    }
+}
@@ -0,0 +1,283 @@
+// Copyright (C) 2022 JET PROTOCOL HOLDINGS, LLC.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde_json::{json, Value};
+
+use crate::config::MutationSettings;
+
+/// Removes every object field with one of the configured names from the result.
+pub fn drop_fields(result: &mut Value, settings: &MutationSettings) {
+    match result {
+        Value::Object(obj) => {
+            obj.retain(|k, _| !settings.drop_fields.contains(k));
+            obj.values_mut().for_each(|v| drop_fields(v, settings));
+        }
+        Value::Array(items) => items.iter_mut().for_each(|v| drop_fields(v, settings)),
+        _ => {}
+    }
+}
+
+/// Truncates every array within the result to the configured length,
+/// or to half of its original length if no length is configured.
+pub fn truncate_arrays(result: &mut Value, settings: &MutationSettings) {
+    match result {
+        Value::Object(obj) => obj.values_mut().for_each(|v| truncate_arrays(v, settings)),
+        Value::Array(items) => {
+            if !is_encoded_data(items) {
+                let len = settings.truncate_length.unwrap_or(items.len() / 2);
+                items.truncate(len);
+            }
+            items.iter_mut().for_each(|v| truncate_arrays(v, settings));
+        }
+        _ => {}
+    }
+}
+
+/// Flips random bits in the configured number of bytes of each
+/// base-64 encoded account `data` field within the result.
+pub fn corrupt_data<R: Rng + ?Sized>(result: &mut Value, settings: &MutationSettings, rng: &mut R) {
+    match result {
+        Value::Object(obj) => {
+            for (k, v) in obj.iter_mut() {
+                match v {
+                    Value::Array(items) if k == "data" && is_encoded_data(items) => {
+                        corrupt_encoded(items, settings.corrupt_bytes, rng)
+                    }
+                    _ => corrupt_data(v, settings, rng),
+                }
+            }
+        }
+        Value::Array(items) => items
+            .iter_mut()
+            .for_each(|v| corrupt_data(v, settings, rng)),
+        _ => {}
+    }
+}
+
+/// Replaces the account values of the result with `null`, keeping
+/// the response context and the length of any array of values.
+pub fn null_values(result: &mut Value) {
+    let target = match result.get_mut("value") {
+        Some(v) => v,
+        None => result,
+    };
+
+    match target {
+        Value::Array(items) => items.iter_mut().for_each(|v| *v = Value::Null),
+        other => *other = Value::Null,
+    }
+}
+
+/// Moves the `context.slot` of the result back by the configured number of slots.
+pub fn stale_slot(result: &mut Value, settings: &MutationSettings) {
+    if let Some(slot) = result.pointer_mut("/context/slot") {
+        if let Some(s) = slot.as_u64() {
+            *slot = Value::from(s.saturating_sub(settings.slot_lag));
+        }
+    }
+}
+
+/// Shuffles the order of the items in the result or its `value` array,
+/// such as the accounts returned by `getProgramAccounts`.
+pub fn reorder<R: Rng + ?Sized>(result: &mut Value, rng: &mut R) {
+    let target = match result.get_mut("value") {
+        Some(v) => v,
+        None => result,
+    };
+
+    if let Value::Array(items) = target {
+        items.shuffle(rng);
+    }
+}
+
+/// Reports the argued transaction error for each marked signature in the result
+/// of a `getSignatureStatuses` or `getTransaction` request, where transactions
+/// that have not landed are left unknown.
+pub fn fail_transactions(result: &mut Value, method: &str, marked: &[bool], err: &Value) {
+    let fail = |target: &mut Value| {
+        if target.is_object() {
+            target["err"] = err.clone();
+            target["status"] = json!({ "Err": err });
+        }
+    };
+
+    match method {
+        "getSignatureStatuses" => {
+            if let Some(Value::Array(statuses)) = result.get_mut("value") {
+                statuses
+                    .iter_mut()
+                    .zip(marked)
+                    .filter(|(_, m)| **m)
+                    .for_each(|(s, _)| fail(s));
+            }
+        }
+        "getTransaction" if marked.first() == Some(&true) => {
+            if let Some(meta) = result.get_mut("meta") {
+                fail(meta);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Returns whether the array is an encoded account data tuple, such as `["AAEC", "base64"]`.
+fn is_encoded_data(items: &[Value]) -> bool {
+    matches!(items, [Value::String(_), Value::String(enc)] if enc.starts_with("base"))
+}
+
+/// Corrupts the bytes of an encoded account data tuple in place.
+fn corrupt_encoded<R: Rng + ?Sized>(items: &mut [Value], count: usize, rng: &mut R) {
+    let (data, encoding) = match items {
+        [Value::String(d), Value::String(e)] => (d, e.clone()),
+        _ => return,
+    };
+
+    let mut bytes = match encoding.as_str() {
+        "base58" => bs58::decode(data.as_str()).into_vec().unwrap_or_default(),
+        _ => base64::decode(data.as_str()).unwrap_or_default(),
+    };
+
+    if bytes.is_empty() {
+        return;
+    }
+
+    for _ in 0..count {
+        let i = rng.gen_range(0..bytes.len());
+        bytes[i] ^= rng.gen_range(1..=u8::MAX);
+    }
+
+    *data = match encoding.as_str() {
+        "base58" => bs58::encode(bytes).into_string(),
+        _ => base64::encode(bytes),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::thread_rng;
+
+    use super::*;
+
+    fn account_info() -> Value {
+        json!({
+            "context": {"slot": 1000},
+            "value": {
+                "data": [base64::encode([1, 2, 3, 4, 5, 6, 7, 8]), "base64"],
+                "executable": false,
+                "lamports": 100,
+                "owner": "11111111111111111111111111111111",
+                "rentEpoch": 5
+            }
+        })
+    }
+
+    #[test]
+    fn drop_and_null() {
+        let settings = MutationSettings {
+            drop_fields: vec!["owner".into(), "rentEpoch".into()],
+            ..Default::default()
+        };
+
+        let mut res = account_info();
+        drop_fields(&mut res, &settings);
+        assert!(res["value"].get("owner").is_none());
+        assert!(res["value"].get("rentEpoch").is_none());
+        assert_eq!(res["value"]["lamports"], 100);
+
+        null_values(&mut res);
+        assert_eq!(res["value"], Value::Null);
+        assert_eq!(res["context"]["slot"], 1000);
+
+        let mut multiple = json!({"context": {"slot": 1}, "value": [{"lamports": 1}, null]});
+        null_values(&mut multiple);
+        assert_eq!(multiple["value"], json!([null, null]));
+    }
+
+    #[test]
+    fn truncate_and_reorder() {
+        let mut res = json!([1, 2, 3, 4, {"data": ["AAAA", "base64"]}, 6]);
+        truncate_arrays(&mut res, &MutationSettings::default());
+        assert_eq!(res, json!([1, 2, 3]));
+
+        let mut res = json!({"value": [1, 2, 3]});
+        truncate_arrays(
+            &mut res,
+            &MutationSettings {
+                truncate_length: Some(1),
+                ..Default::default()
+            },
+        );
+        assert_eq!(res, json!({"value": [1]}));
+
+        let mut accounts: Value = (0..50).collect::<Vec<u32>>().into();
+        reorder(&mut accounts, &mut thread_rng());
+        let mut sorted = accounts.as_array().unwrap().clone();
+        sorted.sort_by_key(|v| v.as_u64());
+        assert_ne!(accounts, Value::from(sorted.clone()));
+        assert_eq!(sorted.len(), 50);
+    }
+
+    #[test]
+    fn corrupt_and_stale() {
+        let settings = MutationSettings::default();
+        let original = account_info();
+
+        let mut res = original.clone();
+        corrupt_data(&mut res, &settings, &mut thread_rng());
+        assert_ne!(res["value"]["data"][0], original["value"]["data"][0]);
+        assert_eq!(res["value"]["data"][1], "base64");
+        assert_eq!(
+            base64::decode(res["value"]["data"][0].as_str().unwrap())
+                .unwrap()
+                .len(),
+            8
+        );
+
+        stale_slot(&mut res, &settings);
+        assert_eq!(res["context"]["slot"], 1000 - settings.slot_lag);
+    }
+
+    #[test]
+    fn failed_transactions() {
+        let err = json!({"InstructionError": [0, {"Custom": 6001}]});
+
+        let mut statuses = json!({
+            "context": {"slot": 10},
+            "value": [
+                {"slot": 5, "confirmations": 5, "err": null, "status": {"Ok": null}},
+                {"slot": 6, "confirmations": 4, "err": null, "status": {"Ok": null}},
+                null
+            ]
+        });
+        fail_transactions(
+            &mut statuses,
+            "getSignatureStatuses",
+            &[false, true, true],
+            &err,
+        );
+        assert_eq!(statuses["value"][0]["err"], Value::Null);
+        assert_eq!(statuses["value"][1]["err"], err);
+        assert_eq!(statuses["value"][1]["status"], json!({ "Err": err }));
+        assert_eq!(statuses["value"][2], Value::Null);
+
+        let mut tx = json!({"slot": 5, "meta": {"err": null, "fee": 5000, "status": {"Ok": null}}});
+        fail_transactions(&mut tx, "getTransaction", &[true], &err);
+        assert_eq!(tx["meta"]["err"], err);
+        assert_eq!(tx["meta"]["status"], json!({ "Err": err }));
+        assert_eq!(tx["meta"]["fee"], 5000);
+    }
+}
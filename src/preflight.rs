@@ -0,0 +1,197 @@
+// Copyright (C) 2022 JET PROTOCOL HOLDINGS, LLC.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use serde_json::{json, Value};
+
+use crate::config::{PreflightError, PreflightSettings};
+use crate::transaction::Transaction;
+
+/// JSON-RPC error code of a `sendTransaction` request that failed its preflight simulation.
+pub const SIMULATION_FAILED: i64 = -32002;
+
+/// Most compute units reported as consumed by an instruction that fails with a program error.
+const PROGRAM_ERROR_UNITS: u64 = 20_000;
+
+/// Program that is reported in generated logs when the transaction could not be decoded.
+const UNKNOWN_PROGRAM: &str = "11111111111111111111111111111111";
+
+/// Returns whether the parameters of a `sendTransaction` request skip the
+/// preflight simulation, in which case a validator never reports its failure.
+pub fn skips_preflight(params: &[Value]) -> bool {
+    params
+        .get(1)
+        .and_then(|c| c.get("skipPreflight"))
+        .and_then(Value::as_bool)
+        .unwrap_or(false)
+}
+
+/// Builds the JSON-RPC error object that a validator returns when the
+/// preflight simulation of the argued transaction fails with the error.
+pub fn simulation_failure(
+    error: &PreflightError,
+    tx: Option<&Transaction>,
+    settings: &PreflightSettings,
+) -> Value {
+    let (err, reason, units) = match error {
+        PreflightError::InstructionError { index, code } => (
+            json!({"InstructionError": [index, {"Custom": code}]}),
+            format!(
+                "Error processing Instruction {}: custom program error: {:#x}",
+                index, code
+            ),
+            settings.units_consumed.min(PROGRAM_ERROR_UNITS),
+        ),
+        PreflightError::InsufficientFundsForFee => (
+            json!("InsufficientFundsForFee"),
+            "Insufficient funds for fee".to_string(),
+            0,
+        ),
+        PreflightError::AccountInUse => (json!("AccountInUse"), "Account in use".to_string(), 0),
+        PreflightError::ComputeBudgetExceeded { index } => (
+            json!({"InstructionError": [index, "ComputationalBudgetExceeded"]}),
+            format!(
+                "Error processing Instruction {}: Computational budget exceeded",
+                index
+            ),
+            settings.units_consumed,
+        ),
+    };
+
+    let logs = match &settings.logs {
+        Some(logs) => logs.clone(),
+        None => program_logs(error, tx, units),
+    };
+
+    json!({
+        "code": SIMULATION_FAILED,
+        "message": format!("Transaction simulation failed: {}", reason),
+        "data": {
+            "accounts": null,
+            "err": err,
+            "logs": logs,
+            "returnData": null,
+            "unitsConsumed": units,
+        },
+    })
+}
+
+/// Generates the program logs of a failed instruction, where errors that
+/// fail before execution, such as fee and lock errors, produce no logs.
+fn program_logs(error: &PreflightError, tx: Option<&Transaction>, units: u64) -> Vec<String> {
+    let (index, failure) = match error {
+        PreflightError::InstructionError { index, code } => {
+            (*index, format!("custom program error: {:#x}", code))
+        }
+        PreflightError::ComputeBudgetExceeded { index } => (
+            *index,
+            "Program failed to complete: exceeded maximum number of instructions allowed"
+                .to_string(),
+        ),
+        _ => return Vec::new(),
+    };
+
+    let program = tx
+        .and_then(|t| t.instructions.get(index as usize))
+        .map_or(UNKNOWN_PROGRAM, |ix| ix.program_id.as_str());
+
+    vec![
+        format!("Program {} invoke [1]", program),
+        format!(
+            "Program {} consumed {} of {} compute units",
+            program,
+            units,
+            units.max(200_000)
+        ),
+        format!("Program {} failed: {}", program, failure),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::tests::wire_transaction_with;
+
+    #[test]
+    fn instruction_error_failure() {
+        let bytes = wire_transaction_with([1; 64], &[[2; 32], [3; 32]], &[(1, &[])]);
+        let tx = Transaction::decode(&bytes).unwrap();
+        let program = bs58::encode([3; 32]).into_string();
+
+        let failure = simulation_failure(
+            &PreflightError::InstructionError {
+                index: 0,
+                code: 6001,
+            },
+            Some(&tx),
+            &PreflightSettings::default(),
+        );
+
+        assert_eq!(failure["code"], SIMULATION_FAILED);
+        assert_eq!(
+            failure["message"],
+            "Transaction simulation failed: Error processing Instruction 0: custom program error: 0x1771"
+        );
+        assert_eq!(
+            failure["data"]["err"],
+            json!({"InstructionError": [0, {"Custom": 6001}]})
+        );
+        assert_eq!(
+            failure["data"]["logs"][2],
+            format!("Program {} failed: custom program error: 0x1771", program)
+        );
+    }
+
+    #[test]
+    fn fee_and_configured_log_failures() {
+        let settings = PreflightSettings {
+            logs: Some(vec!["Program log: custom".into()]),
+            ..Default::default()
+        };
+
+        let fee = simulation_failure(
+            &PreflightError::InsufficientFundsForFee,
+            None,
+            &PreflightSettings::default(),
+        );
+        assert_eq!(fee["data"]["err"], "InsufficientFundsForFee");
+        assert_eq!(fee["data"]["logs"], json!([]));
+        assert_eq!(fee["data"]["unitsConsumed"], 0);
+
+        let budget = simulation_failure(
+            &PreflightError::ComputeBudgetExceeded { index: 1 },
+            None,
+            &settings,
+        );
+        assert_eq!(
+            budget["data"]["err"],
+            json!({"InstructionError": [1, "ComputationalBudgetExceeded"]})
+        );
+        assert_eq!(budget["data"]["logs"], json!(["Program log: custom"]));
+        assert_eq!(budget["data"]["unitsConsumed"], 200_000);
+    }
+
+    #[test]
+    fn skip_preflight_flag() {
+        assert!(!skips_preflight(&[json!("tx")]));
+        assert!(!skips_preflight(&[
+            json!("tx"),
+            json!({"encoding": "base64"})
+        ]));
+        assert!(skips_preflight(&[
+            json!("tx"),
+            json!({"skipPreflight": true})
+        ]));
+    }
+}
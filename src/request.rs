@@ -0,0 +1,283 @@
+// Copyright (C) 2022 JET PROTOCOL HOLDINGS, LLC.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use actix_web::web;
+use serde::Serialize;
+use serde_json::Value;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::transaction::Transaction;
+
+/// The reasoning behind the proxy's handling decision
+/// for an incoming RPC request.
+#[derive(Clone, Debug, Serialize)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum Reason {
+    /// No fault was selected and the request was forwarded upstream.
+    Passthrough,
+    /// The random draw for the request fell within the rate of a configured fault rule.
+    Rule {
+        index: usize,
+        draw: f32,
+        threshold: f32,
+    },
+    /// The random draw for the request exceeded the RPC success rate.
+    RpcSuccessRate { draw: f32, threshold: f32 },
+    /// The random draw for the transaction exceeded the transaction success rate.
+    TxSuccessRate { draw: f32, threshold: f32 },
+    /// The random draw for the airdrop exceeded the airdrop success rate.
+    AirdropSuccessRate { draw: f32, threshold: f32 },
+    /// The requested signature was previously falsified by the proxy.
+    FakeSignature,
+    /// The sent transaction was previously dropped by the proxy.
+    DroppedSignature,
+    /// A requested signature was previously chosen to be reported as failed.
+    FailedSignature,
+    /// A requested signature or account was affected by a transaction on a simulated fork.
+    Forked,
+    /// The simulated cluster halt was in progress.
+    Halted,
+    /// The simulated validator restart was in progress.
+    Restarting,
+    /// The request was forwarded by the proxy on behalf of the earlier request with the argued id.
+    Delayed {
+        #[serde(rename = "requestId")]
+        request_id: u64,
+    },
+}
+
+/// Mutable details of how the proxy handled a request that are
+/// collected while the request moves through the service.
+#[derive(Clone, Debug)]
+pub struct Trace {
+    pub event: Option<String>,
+    pub reason: Reason,
+    pub injected_delay: Duration,
+    pub streaming: Duration,
+    pub upstream_status: Option<u16>,
+    pub signature: Option<String>,
+}
+
+impl Default for Trace {
+    fn default() -> Self {
+        Self {
+            event: None,
+            reason: Reason::Passthrough,
+            injected_delay: Duration::ZERO,
+            streaming: Duration::ZERO,
+            upstream_status: None,
+            signature: None,
+        }
+    }
+}
+
+/// An incoming JSON-RPC request to the proxy with its parsed body
+/// and the trace of the proxy's handling of it.
+#[derive(Debug)]
+pub struct RpcRequest {
+    pub id: u64,
+    pub client: Option<SocketAddr>,
+    pub payload: web::Bytes,
+    pub body: Value,
+    pub method: String,
+    pub transaction: Option<Transaction>,
+    pub started: Instant,
+    trace: Mutex<Trace>,
+}
+
+impl RpcRequest {
+    /// Parses the argued payload into a new `RpcRequest`.
+    pub fn parse(
+        id: u64,
+        client: Option<SocketAddr>,
+        payload: web::Bytes,
+    ) -> Result<Self, serde_json::Error> {
+        let body: Value = serde_json::from_slice(payload.as_ref())?;
+        let method = body
+            .get("method")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+
+        let transaction = match method.as_str() {
+            "sendTransaction" | "simulateTransaction" => body
+                .get("params")
+                .and_then(Value::as_array)
+                .and_then(|p| Transaction::from_params(p)),
+            _ => None,
+        };
+
+        Ok(Self {
+            id,
+            client,
+            payload,
+            body,
+            method,
+            transaction,
+            started: Instant::now(),
+            trace: Mutex::new(Trace::default()),
+        })
+    }
+
+    /// Returns the id of the request, or null if none was provided.
+    pub fn id(&self) -> Value {
+        self.body.get("id").cloned().unwrap_or(Value::Null)
+    }
+
+    /// Returns the positional parameters of the request, if any were provided.
+    pub fn params(&self) -> &[Value] {
+        self.body
+            .get("params")
+            .and_then(Value::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Returns the transaction signatures that the request queries the status of.
+    pub fn queried_signatures(&self) -> Vec<&str> {
+        let first = self.params().first();
+        match self.method.as_str() {
+            "getSignatureStatuses" => first
+                .and_then(Value::as_array)
+                .map(|sigs| sigs.iter().filter_map(Value::as_str).collect())
+                .unwrap_or_default(),
+            "getTransaction" => first.and_then(Value::as_str).into_iter().collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Returns the accounts that the request reads by their public keys.
+    pub fn queried_accounts(&self) -> Vec<&str> {
+        let first = self.params().first();
+        match self.method.as_str() {
+            "getAccountInfo" | "getBalance" | "getTokenAccountBalance" => {
+                first.and_then(Value::as_str).into_iter().collect()
+            }
+            "getMultipleAccounts" => first
+                .and_then(Value::as_array)
+                .map(|keys| keys.iter().filter_map(Value::as_str).collect())
+                .unwrap_or_default(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Returns the commitment level that the request's configuration object asks for, if any.
+    pub fn commitment(&self) -> Option<&str> {
+        self.params()
+            .iter()
+            .rev()
+            .find_map(|p| p.get("commitment"))
+            .and_then(Value::as_str)
+    }
+
+    /// Returns a snapshot of the current request handling trace.
+    pub fn trace(&self) -> Trace {
+        self.trace.lock().unwrap().clone()
+    }
+
+    /// Records the event that was selected to be injected and why.
+    pub fn decide(&self, event: impl ToString, reason: Reason) {
+        let mut t = self.trace.lock().unwrap();
+        t.event = Some(event.to_string());
+        t.reason = reason;
+    }
+
+    /// Adds the argued duration to the total injected delay of the request.
+    pub fn add_delay(&self, d: Duration) {
+        self.trace.lock().unwrap().injected_delay += d;
+    }
+
+    /// Records how long the response body is streamed to the client
+    /// for after the request has been handled.
+    pub fn set_streaming(&self, d: Duration) {
+        self.trace.lock().unwrap().streaming = d;
+    }
+
+    /// Records the HTTP status code returned by the upstream endpoint.
+    pub fn set_upstream_status(&self, status: u16) {
+        self.trace.lock().unwrap().upstream_status = Some(status);
+    }
+
+    /// Records a transaction signature that was returned by the proxy.
+    pub fn set_signature(&self, sig: impl Into<String>) {
+        self.trace.lock().unwrap().signature = Some(sig.into());
+    }
+}
+
+impl Default for RpcRequest {
+    fn default() -> Self {
+        Self {
+            id: 0,
+            client: None,
+            payload: web::Bytes::default(),
+            body: Value::Null,
+            method: String::new(),
+            transaction: None,
+            started: Instant::now(),
+            trace: Mutex::new(Trace::default()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn parse_request() {
+        let payload = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "getBalance",
+            "params": ["11111111111111111111111111111111"]
+        })
+        .to_string();
+
+        let req = RpcRequest::parse(7, None, payload.into()).unwrap();
+
+        assert_eq!(req.id, 7);
+        assert_eq!(req.method, "getBalance");
+        assert_eq!(req.params(), &[json!("11111111111111111111111111111111")]);
+        assert_eq!(req.commitment(), None);
+        assert_eq!(req.trace().reason, Reason::Passthrough);
+
+        assert!(RpcRequest::parse(8, None, "not json".into()).is_err());
+    }
+
+    #[test]
+    fn trace_updates() {
+        let req = RpcRequest::default();
+        req.decide(
+            "RateLimit",
+            Reason::RpcSuccessRate {
+                draw: 0.9,
+                threshold: 0.65,
+            },
+        );
+        req.add_delay(Duration::from_secs(1));
+        req.add_delay(Duration::from_secs(2));
+        req.set_upstream_status(200);
+
+        let t = req.trace();
+        assert_eq!(t.event.as_deref(), Some("RateLimit"));
+        assert_eq!(t.injected_delay, Duration::from_secs(3));
+        assert_eq!(t.upstream_status, Some(200));
+    }
+}
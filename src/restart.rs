@@ -0,0 +1,99 @@
+// Copyright (C) 2022 JET PROTOCOL HOLDINGS, LLC.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::time::{Duration, Instant};
+
+use crate::config::RestartSettings;
+
+/// Stage of a simulated validator restart as seen by clients.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Stage {
+    /// The validator is down and its listener refuses connections.
+    Down,
+    /// The validator is up but still behind the cluster by the argued number of slots.
+    CatchingUp { slots_behind: u64 },
+    /// The validator has caught up and requests are passed through again.
+    Done,
+}
+
+/// A simulated validator restart that moves through its stages
+/// based on the time elapsed since it was triggered.
+#[derive(Clone, Debug)]
+pub struct Restart {
+    pub triggered: Instant,
+    pub settings: RestartSettings,
+}
+
+impl Restart {
+    /// Triggers a new restart with the argued stage durations at the current time.
+    pub fn new(settings: RestartSettings) -> Self {
+        Self {
+            triggered: Instant::now(),
+            settings,
+        }
+    }
+
+    /// Returns how long the listener refuses connections for.
+    pub fn downtime(&self) -> Duration {
+        Duration::from_millis(self.settings.down_ms)
+    }
+
+    /// Returns the current stage of the restart, where the number of slots
+    /// behind shrinks linearly to zero over the catch-up stage.
+    pub fn stage(&self) -> Stage {
+        self.stage_at(self.triggered.elapsed())
+    }
+
+    fn stage_at(&self, elapsed: Duration) -> Stage {
+        let down = self.downtime();
+        let catch_up = Duration::from_millis(self.settings.catch_up_ms);
+
+        if elapsed < down {
+            Stage::Down
+        } else if elapsed < down + catch_up {
+            let remaining = (down + catch_up - elapsed).as_secs_f64() / catch_up.as_secs_f64();
+            Stage::CatchingUp {
+                slots_behind: (self.settings.slots_behind as f64 * remaining).ceil() as u64,
+            }
+        } else {
+            Stage::Done
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restart_stages() {
+        let restart = Restart::new(RestartSettings {
+            down_ms: 1_000,
+            catch_up_ms: 2_000,
+            slots_behind: 100,
+        });
+
+        assert_eq!(restart.stage_at(Duration::ZERO), Stage::Down);
+        assert_eq!(
+            restart.stage_at(Duration::from_millis(1_000)),
+            Stage::CatchingUp { slots_behind: 100 }
+        );
+        assert_eq!(
+            restart.stage_at(Duration::from_millis(2_500)),
+            Stage::CatchingUp { slots_behind: 25 }
+        );
+        assert_eq!(restart.stage_at(Duration::from_millis(3_000)), Stage::Done);
+    }
+}
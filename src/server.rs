@@ -0,0 +1,505 @@
+// Copyright (C) 2022 JET PROTOCOL HOLDINGS, LLC.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use actix_web::dev::{self, ServerHandle};
+use actix_web::{middleware, web, App, HttpServer};
+use futures_util::future::{self, Either};
+use futures_util::pin_mut;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+use crate::config::{AccountOverride, Config, FaultSettings, PercentageSettings};
+use crate::event_log::{EventFilter, EventRecord};
+use crate::fault::Fault;
+use crate::restart::Restart;
+use crate::service::GlobalState;
+use crate::{admin, service};
+
+/// Registers all of the proxy's HTTP services on an actix `App`.
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(admin::accounts)
+        .service(admin::set_account)
+        .service(admin::clear_account)
+        .service(admin::events)
+        .service(admin::reset_events)
+        .service(admin::halt)
+        .service(admin::resume)
+        .service(admin::restart)
+        .service(service::health)
+        .service(service::metrics)
+        .service(service::rpc);
+}
+
+/// Builder for starting the proxy server in the background,
+/// either as the main binary or embedded within a test.
+///
+/// # Example
+///
+/// ```
+/// use atc::config::{Config, PercentageSettings};
+/// use atc::ServerBuilder;
+///
+/// # #[actix_rt::main]
+/// # async fn main() {
+/// let server = ServerBuilder::new(Config {
+///     mock_upstream: true,
+///     ..Default::default()
+/// })
+/// .port(0)
+/// .workers(1)
+/// .start()
+/// .unwrap();
+///
+/// server.set_percentages(PercentageSettings {
+///     rpc_success: 1.0,
+///     tx_success: 1.0,
+///     airdrop_success: 1.0,
+/// });
+///
+/// let res = reqwest::Client::new()
+///     .post(server.url())
+///     .header("Content-Type", "application/json")
+///     .body(r#"{"jsonrpc":"2.0","id":1,"method":"getHealth"}"#)
+///     .send()
+///     .await
+///     .unwrap();
+///
+/// assert!(res.status().is_success());
+/// # }
+/// ```
+pub struct ServerBuilder {
+    config: Config,
+    faults: Vec<Arc<dyn Fault>>,
+    host: String,
+    state: Option<GlobalState>,
+}
+
+impl ServerBuilder {
+    /// Creates a new builder for a server with the argued configuration
+    /// that listens on the loopback interface.
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            faults: Vec::new(),
+            host: "127.0.0.1".into(),
+            state: None,
+        }
+    }
+
+    /// Sets the host address for the server to bind to.
+    pub fn host(mut self, host: impl Into<String>) -> Self {
+        self.host = host.into();
+        self
+    }
+
+    /// Sets the port for the server to listen on, where `0` binds an ephemeral port.
+    pub fn port(mut self, port: u16) -> Self {
+        self.config.port = port;
+        self
+    }
+
+    /// Sets the number of parallel workers for the server to run.
+    pub fn workers(mut self, workers: usize) -> Self {
+        self.config.workers = workers;
+        self
+    }
+
+    /// Registers a custom fault that the configured fault rules and weights can refer to.
+    pub fn fault(mut self, fault: impl Fault + 'static) -> Self {
+        self.faults.push(Arc::new(fault));
+        self
+    }
+
+    /// Uses the argued shared state instead of building one from the configuration.
+    pub fn state(mut self, state: GlobalState) -> Self {
+        self.state = Some(state);
+        self
+    }
+
+    /// Binds the server's listener and spawns it onto the current async runtime.
+    pub fn start(self) -> std::io::Result<Server> {
+        let state = web::Data::new(match self.state {
+            Some(s) => s,
+            None => GlobalState::try_from(self.config.clone())?,
+        });
+
+        for fault in self.faults {
+            state.faults.register_arc(fault);
+        }
+        state
+            .faults
+            .validate(&state.fault_settings.read().unwrap())?;
+
+        let (srv, addr) = bind(
+            &state,
+            (self.host.as_str(), self.config.port),
+            self.config.workers,
+        )?;
+        let handle = Arc::new(Mutex::new(srv.handle()));
+        let stopping = Arc::new(AtomicBool::new(false));
+
+        Ok(Server {
+            addr,
+            handle: handle.clone(),
+            state: state.clone(),
+            stopping: stopping.clone(),
+            task: tokio::spawn(supervise(
+                srv,
+                addr,
+                self.config.workers,
+                state,
+                handle,
+                stopping,
+            )),
+        })
+    }
+}
+
+/// Binds a listener for the proxy's HTTP services at the argued address
+/// and returns the server that runs it along with the bound address.
+fn bind(
+    state: &web::Data<GlobalState>,
+    addr: impl ToSocketAddrs,
+    workers: usize,
+) -> std::io::Result<(dev::Server, SocketAddr)> {
+    let shared_data = state.clone();
+    let http = HttpServer::new(move || {
+        App::new()
+            .wrap(middleware::Compress::default())
+            .wrap(middleware::Logger::default())
+            .app_data(shared_data.clone())
+            .configure(routes)
+    })
+    .bind(addr)?
+    .workers(workers);
+
+    let addr = http.addrs()[0];
+    Ok((http.run(), addr))
+}
+
+/// Runs the server until it exits, stopping its listener whenever a simulated
+/// validator restart is triggered and binding it again after the downtime.
+async fn supervise(
+    mut srv: dev::Server,
+    addr: SocketAddr,
+    workers: usize,
+    state: web::Data<GlobalState>,
+    handle: Arc<Mutex<ServerHandle>>,
+    stopping: Arc<AtomicBool>,
+) -> std::io::Result<()> {
+    loop {
+        let restarted = state.restart_signal.notified();
+        pin_mut!(restarted);
+        if let Either::Left((res, _)) = future::select(&mut srv, restarted).await {
+            return res;
+        }
+
+        let downtime = state
+            .restarting
+            .read()
+            .unwrap()
+            .as_ref()
+            .map(Restart::downtime)
+            .unwrap_or_default();
+
+        // Give the response of the triggering request a moment to flush before
+        // dropping every connection, as a crashing validator would.
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        // The stop command is sent eagerly and only completes while the server is polled.
+        drop(handle.lock().unwrap().stop(false));
+        srv.await?;
+        tokio::time::sleep(downtime).await;
+
+        if stopping.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+
+        let (next, _) = bind(&state, addr, workers)?;
+        *handle.lock().unwrap() = next.handle();
+        srv = next;
+    }
+}
+
+/// Handle to a running proxy server for controlling its injected
+/// faults and reading its event history, which stops the server once dropped.
+pub struct Server {
+    addr: SocketAddr,
+    handle: Arc<Mutex<ServerHandle>>,
+    state: web::Data<GlobalState>,
+    stopping: Arc<AtomicBool>,
+    task: JoinHandle<std::io::Result<()>>,
+}
+
+impl Server {
+    /// Returns the socket address that the server is listening on.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Returns the HTTP URL of the server's RPC endpoint.
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Returns the shared state of the running server.
+    pub fn state(&self) -> &web::Data<GlobalState> {
+        &self.state
+    }
+
+    /// Replaces the RPC and transaction success rates of the running server.
+    pub fn set_percentages(&self, p: PercentageSettings) {
+        *self.state.percentages.write().unwrap() = p;
+    }
+
+    /// Replaces the fault selection weights and rules of the running server,
+    /// or returns an error if they refer to a fault that is not registered.
+    pub fn set_faults(&self, f: FaultSettings) -> std::io::Result<()> {
+        self.state.faults.validate(&f)?;
+        *self.state.fault_settings.write().unwrap() = f;
+        Ok(())
+    }
+
+    /// Returns the injected events recorded by the server that match the filter.
+    pub fn events(&self, filter: &EventFilter) -> Vec<EventRecord> {
+        self.state.event_log.history(filter)
+    }
+
+    /// Discards the injected event history recorded by the server.
+    pub fn clear_events(&self) {
+        self.state.event_log.clear_history();
+    }
+
+    /// Overrides the state of the account with the argued pubkey on the running server.
+    pub fn set_account(&self, pubkey: impl Into<String>, account: AccountOverride) {
+        self.state
+            .accounts
+            .write()
+            .unwrap()
+            .insert(pubkey.into(), account);
+    }
+
+    /// Removes the override of the account with the argued pubkey on the running server.
+    pub fn clear_account(&self, pubkey: &str) {
+        self.state.accounts.write().unwrap().remove(pubkey);
+    }
+
+    /// Starts a simulated cluster halt on the running server.
+    pub fn halt(&self) {
+        self.state.halt();
+    }
+
+    /// Ends the simulated cluster halt on the running server.
+    pub fn resume(&self) {
+        self.state.resume();
+    }
+
+    /// Triggers a simulated validator restart on the running server.
+    pub fn restart(&self) {
+        self.state.restart();
+    }
+
+    /// Gracefully stops the server and waits for it to exit.
+    pub async fn stop(self) {
+        self.stopping.store(true, Ordering::SeqCst);
+        let handle = self.handle.lock().unwrap().clone();
+        handle.stop(true).await;
+    }
+
+    /// Waits for the server to exit on its own, such as from a shutdown signal.
+    pub async fn wait(mut self) -> std::io::Result<()> {
+        (&mut self.task).await.map_err(std::io::Error::other)?
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        // The stop command is sent eagerly, so the completion future can be dropped.
+        self.stopping.store(true, Ordering::SeqCst);
+        drop(self.handle.lock().unwrap().stop(false));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::config::RestartSettings;
+    use crate::event::NODE_UNHEALTHY;
+
+    async fn post(url: &str, body: Value) -> reqwest::Response {
+        reqwest::Client::new()
+            .post(url)
+            .header("Content-Type", "application/json")
+            .body(body.to_string())
+            .send()
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn unknown_faults() {
+        let unknown = FaultSettings {
+            weights: [("Teapot".to_string(), 1)].into_iter().collect(),
+            rules: Vec::new(),
+        };
+
+        assert!(ServerBuilder::new(Config {
+            mock_upstream: true,
+            faults: unknown.clone(),
+            ..Default::default()
+        })
+        .port(0)
+        .workers(1)
+        .start()
+        .is_err());
+
+        let server = ServerBuilder::new(Config {
+            mock_upstream: true,
+            ..Default::default()
+        })
+        .port(0)
+        .workers(1)
+        .start()
+        .unwrap();
+
+        assert!(server.set_faults(unknown).is_err());
+        assert!(server.set_faults(FaultSettings::default()).is_ok());
+    }
+
+    #[tokio::test]
+    async fn embedded_harness() {
+        let server = ServerBuilder::new(Config {
+            mock_upstream: true,
+            ..Default::default()
+        })
+        .port(0)
+        .workers(1)
+        .start()
+        .unwrap();
+
+        assert_ne!(server.addr().port(), 0);
+
+        server.set_percentages(PercentageSettings {
+            tx_success: 0.0,
+            ..PercentageSettings::all_success()
+        });
+
+        let res = post(
+            &server.url(),
+            json!({"jsonrpc": "2.0", "id": 1, "method": "sendTransaction", "params": [""]}),
+        )
+        .await;
+        assert_eq!(
+            res.headers().get("X-ATC-Event").unwrap(),
+            "FalsifiedSignature"
+        );
+
+        let events = server.events(&EventFilter::default());
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].method, "sendTransaction");
+
+        server.clear_events();
+        server.set_percentages(PercentageSettings::all_success());
+
+        let slot = post(
+            &server.url(),
+            json!({"jsonrpc": "2.0", "id": 1, "method": "getSlot"}),
+        )
+        .await
+        .bytes()
+        .await
+        .unwrap();
+        let slot: Value = serde_json::from_slice(&slot).unwrap();
+        assert!(slot["result"].is_u64());
+        assert!(server.events(&EventFilter::default()).is_empty());
+
+        let url = server.url();
+        drop(server);
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        assert!(reqwest::Client::new()
+            .post(url)
+            .body("{}")
+            .send()
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn validator_restart() {
+        let server = ServerBuilder::new(Config {
+            mock_upstream: true,
+            restart: RestartSettings {
+                down_ms: 500,
+                catch_up_ms: 1_000,
+                slots_behind: 100,
+            },
+            ..Default::default()
+        })
+        .port(0)
+        .workers(1)
+        .start()
+        .unwrap();
+
+        server.set_percentages(PercentageSettings::all_success());
+
+        let health = json!({"jsonrpc": "2.0", "id": 1, "method": "getHealth"});
+        let res = post(&format!("{}/admin/restart", server.url()), json!({})).await;
+        assert_eq!(res.status(), 204);
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(reqwest::Client::new()
+            .post(server.url())
+            .header("Content-Type", "application/json")
+            .body(health.to_string())
+            .send()
+            .await
+            .is_err());
+
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        let res = post(&server.url(), health.clone()).await;
+        assert_eq!(
+            res.headers().get("X-ATC-Event").unwrap(),
+            "ValidatorRestart"
+        );
+        let body: Value = serde_json::from_slice(&res.bytes().await.unwrap()).unwrap();
+        assert_eq!(body["error"]["code"], NODE_UNHEALTHY);
+        let first = body["error"]["data"]["numSlotsBehind"].as_u64().unwrap();
+        assert!(first > 0 && first <= 100);
+
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        let body: Value = serde_json::from_slice(
+            &post(&server.url(), health.clone())
+                .await
+                .bytes()
+                .await
+                .unwrap(),
+        )
+        .unwrap();
+        assert!(body["error"]["data"]["numSlotsBehind"].as_u64().unwrap() < first);
+
+        tokio::time::sleep(Duration::from_millis(700)).await;
+        let body: Value =
+            serde_json::from_slice(&post(&server.url(), health).await.bytes().await.unwrap())
+                .unwrap();
+        assert_eq!(body["result"], "ok");
+
+        server.stop().await;
+    }
+}
@@ -13,48 +13,224 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use actix_web::{get, post, web, HttpResponse, HttpResponseBuilder};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::http::StatusCode;
+use actix_web::{get, post, web, HttpRequest, HttpResponse, HttpResponseBuilder};
 use rand::{thread_rng, Rng};
-use std::sync::RwLock;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::AtomicUsize;
+use std::sync::{Arc, RwLock};
+use std::time::Instant;
+use tokio::sync::Notify;
 
-use crate::config::{Config, PercentageSettings};
-use crate::event::RpcEvent;
+use crate::account::{self, OVERRIDDEN_METHODS};
+use crate::blockhash::{BlockhashCache, SeenBlockhash};
+use crate::config::{
+    AccountOverride, AirdropSettings, BlockhashSettings, CommitmentSettings, Config,
+    CongestionSettings, DropSettings, DuplicateSettings, FailureSettings, FaultSettings,
+    ForkSettings, MutationSettings, PercentageSettings, PreflightSettings, RestartSettings,
+    ThrottleSettings, ViewSettings,
+};
+use crate::event::{current_slot, RpcEvent};
+use crate::event_log::EventLog;
+use crate::fault::{choose_weighted, Fault, FaultRegistry};
+use crate::fork::ForkTracker;
+use crate::halt::Halt;
+use crate::metrics::Metrics;
+use crate::request::{Reason, RpcRequest};
+use crate::restart::{Restart, Stage};
+use crate::upstream::Upstream;
+use crate::view::SEEN_RETENTION;
 
 /// The shared global application state to be used for internal
 /// proxy service tracking of RPC event interception details
 /// and external targets.
 #[derive(Default)]
 pub struct GlobalState {
+    pub accounts: RwLock<BTreeMap<String, AccountOverride>>,
+    pub airdrop: AirdropSettings,
+    pub blockhash: BlockhashSettings,
+    pub blockhashes: BlockhashCache,
+    pub congestion: CongestionSettings,
+    pub commitment: CommitmentSettings,
+    pub commitment_seen: RwLock<HashMap<String, Instant>>,
+    pub dropped_signatures: RwLock<HashMap<String, Instant>>,
+    pub drops: DropSettings,
+    pub duplicates: DuplicateSettings,
+    pub event_log: EventLog,
+    pub failed_signatures: RwLock<HashMap<String, Instant>>,
+    pub failures: FailureSettings,
     pub fake_signatures: RwLock<Vec<String>>,
-    pub percentages: PercentageSettings,
-    pub rpc_endpoint: String,
+    pub fault_settings: RwLock<FaultSettings>,
+    pub faults: FaultRegistry,
+    pub fork: ForkSettings,
+    pub forks: ForkTracker,
+    pub halt: RwLock<Option<Arc<Halt>>>,
+    pub metrics: Metrics,
+    pub mutations: MutationSettings,
+    pub throttle: ThrottleSettings,
+    pub percentages: RwLock<PercentageSettings>,
+    pub preflight: PreflightSettings,
+    pub restart: RestartSettings,
+    pub restart_signal: Notify,
+    pub restarting: RwLock<Option<Restart>>,
+    pub upstream: Upstream,
+    pub view_counter: AtomicUsize,
+    pub view_seen: RwLock<HashMap<String, Instant>>,
+    pub views: ViewSettings,
 }
 
-impl From<Config> for GlobalState {
-    fn from(c: Config) -> Self {
-        Self {
+impl TryFrom<Config> for GlobalState {
+    type Error = std::io::Error;
+
+    fn try_from(c: Config) -> Result<Self, Self::Error> {
+        Ok(Self {
+            accounts: RwLock::new(c.accounts.clone()),
+            airdrop: c.airdrop.clone(),
+            blockhash: c.blockhash.clone(),
+            blockhashes: BlockhashCache::default(),
+            congestion: c.congestion.clone(),
+            commitment: c.commitment.clone(),
+            commitment_seen: RwLock::new(HashMap::new()),
+            dropped_signatures: RwLock::new(HashMap::new()),
+            drops: c.drops.clone(),
+            duplicates: c.duplicates.clone(),
+            event_log: EventLog::try_from(&c.event_log)?,
+            failed_signatures: RwLock::new(HashMap::new()),
+            failures: c.failures.clone(),
             fake_signatures: RwLock::new(Vec::new()),
-            percentages: c.percentages,
-            rpc_endpoint: c.rpc_endpoint,
+            fault_settings: RwLock::new(c.faults.clone()),
+            faults: FaultRegistry::default(),
+            fork: c.fork.clone(),
+            forks: ForkTracker::default(),
+            halt: RwLock::new(None),
+            metrics: Metrics::default(),
+            mutations: c.mutations.clone(),
+            throttle: c.throttle.clone(),
+            upstream: Upstream::try_from(&c)?,
+            preflight: c.preflight.clone(),
+            restart: c.restart.clone(),
+            restart_signal: Notify::new(),
+            restarting: RwLock::new(None),
+            view_counter: AtomicUsize::new(0),
+            view_seen: RwLock::new(HashMap::new()),
+            views: c.views.clone(),
+            percentages: RwLock::new(c.percentages),
+        })
+    }
+}
+
+impl GlobalState {
+    /// Starts a simulated cluster halt unless one is already in progress.
+    pub fn halt(&self) {
+        let mut halt = self.halt.write().unwrap();
+        if halt.is_none() {
+            *halt = Some(Arc::new(Halt::new()));
         }
     }
+
+    /// Ends the simulated cluster halt, after which requests are passed
+    /// through and the chain view catches up with the upstream.
+    pub fn resume(&self) {
+        *self.halt.write().unwrap() = None;
+    }
+
+    /// Returns the simulated cluster halt that is in progress, if any.
+    pub fn halted(&self) -> Option<Arc<Halt>> {
+        self.halt.read().unwrap().clone()
+    }
+
+    /// Triggers a simulated validator restart unless one is already in progress,
+    /// which signals a running server to refuse connections for the downtime.
+    pub fn restart(&self) {
+        if self.restart_stage().is_some() {
+            return;
+        }
+        *self.restarting.write().unwrap() = Some(Restart::new(self.restart.clone()));
+        self.restart_signal.notify_one();
+    }
+
+    /// Returns the stage of the simulated validator restart in progress, if any.
+    pub fn restart_stage(&self) -> Option<Stage> {
+        let stage = self.restarting.read().unwrap().as_ref().map(Restart::stage);
+        match stage {
+            Some(Stage::Done) => {
+                *self.restarting.write().unwrap() = None;
+                None
+            }
+            other => other,
+        }
+    }
+
+    /// Returns whether the argued transaction signature was dropped by
+    /// the proxy and has not yet expired or landed upstream.
+    pub fn is_dropped(&self, sig: &str) -> bool {
+        let dropped = self.dropped_signatures.read().unwrap();
+        matches!(dropped.get(sig), Some(until) if *until > Instant::now())
+    }
+
+    /// Returns whether the argued signature was chosen to be reported as failed
+    /// and was sent recently enough that its blockhash could still be valid.
+    pub fn is_failed(&self, sig: &str) -> bool {
+        let failed = self.failed_signatures.read().unwrap();
+        matches!(failed.get(sig), Some(sent) if sent.elapsed() < SEEN_RETENTION)
+    }
 }
 
 /// HTTP responder function to perform a simple request passthrough
 /// to the validator that the proxy is fronting to get an non-manipulated
 /// RPC method reponse to the incoming or constructed request.
 pub async fn passthrough(
-    payload: &web::Bytes,
+    req: &RpcRequest,
     data: &web::Data<GlobalState>,
 ) -> Result<HttpResponse, Box<dyn std::error::Error>> {
-    let res = reqwest::Client::new()
-        .post(data.rpc_endpoint.clone())
-        .header(reqwest::header::CONTENT_TYPE, "application/json")
-        .body(payload.clone())
-        .send()
-        .await?;
-
-    Ok(HttpResponseBuilder::new(res.status()).body(res.text().await?))
+    let (status, body) = forward(req, data).await?;
+    Ok(HttpResponseBuilder::new(status).body(body))
+}
+
+/// Sends the request to the upstream and records the response details
+/// in the request trace and proxy metrics, returning the raw status and
+/// body for events that need to inspect or alter the upstream response.
+pub async fn forward(
+    req: &RpcRequest,
+    data: &web::Data<GlobalState>,
+) -> Result<(StatusCode, web::Bytes), Box<dyn std::error::Error>> {
+    let start = Instant::now();
+    let (status, mut body) = data.upstream.call(req).await?;
+
+    req.set_upstream_status(status.as_u16());
+    if OVERRIDDEN_METHODS.contains(&req.method.as_str()) {
+        let overrides = data.accounts.read().unwrap().clone();
+        if let (false, Ok(mut res)) = (
+            overrides.is_empty(),
+            serde_json::from_slice::<serde_json::Value>(&body),
+        ) {
+            // Accounts that only exist by their override have no upstream result to apply it to.
+            if account::creates(&res, req, &overrides) {
+                account::create(&mut res, current_slot(data).await);
+            }
+            account::apply(&mut res, req, &overrides);
+            body = res.to_string().into();
+        }
+    }
+    if req.method == "getLatestBlockhash" {
+        let seen = serde_json::from_slice::<serde_json::Value>(&body)
+            .ok()
+            .and_then(|res| SeenBlockhash::from_result(&res["result"]));
+        if let Some(bh) = seen {
+            data.blockhashes.observe(bh);
+        }
+    }
+
+    data.metrics
+        .upstream_latency
+        .observe(start.elapsed().as_secs_f64());
+    data.metrics
+        .upstream_responses
+        .with_label_values(&[status.as_str()])
+        .inc();
+
+    Ok((status, body))
 }
 
 #[get("/health")]
@@ -62,55 +238,283 @@ pub async fn health() -> HttpResponse {
     HttpResponse::Ok().finish()
 }
 
+#[get("/metrics")]
+pub async fn metrics(
+    data: web::Data<GlobalState>,
+) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    data.metrics
+        .fake_signatures
+        .set(data.fake_signatures.read().unwrap().len() as i64);
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(data.metrics.render()?))
+}
+
 #[post("/")]
 pub async fn rpc(
+    http_req: HttpRequest,
     payload: web::Bytes,
     data: web::Data<GlobalState>,
 ) -> Result<HttpResponse, Box<dyn std::error::Error>> {
-    dbg!(&payload);
+    let req = RpcRequest::parse(data.event_log.next_id(), http_req.peer_addr(), payload)?;
+    let res = handle(&req, &data).await;
+
+    data.event_log
+        .record(&req, res.as_ref().err().map(ToString::to_string));
 
-    let req: serde_json::Value = serde_json::from_slice(payload.as_ref())?;
-    let method = req.get("method").unwrap().as_str().unwrap();
+    res
+}
 
+/// Selects and injects the event for the incoming request
+/// if one is chosen, otherwise passes the request through
+/// to the upstream RPC endpoint.
+async fn handle(
+    req: &RpcRequest,
+    data: &web::Data<GlobalState>,
+) -> Result<HttpResponse, Box<dyn std::error::Error>> {
     let mut rng = thread_rng();
+    let percentages = data.percentages.read().unwrap().clone();
+    let settings = data.fault_settings.read().unwrap().clone();
+
+    data.metrics
+        .requests
+        .with_label_values(&[&req.method])
+        .inc();
+
+    if let (Some(tx), "sendTransaction") = (&req.transaction, req.method.as_str()) {
+        req.set_signature(tx.signature());
+
+        // Retries of a dropped transaction are dropped again until it expires or lands.
+        if data.is_dropped(tx.signature()) {
+            let event = &RpcEvent::DroppedTransaction;
+            return inject(event, Reason::DroppedSignature, req, data).await;
+        }
+
+        // Retries of a forked transaction are answered by the fork until it lands again.
+        if data.forks.is_forked(tx.signature(), &data.fork) {
+            return inject(&RpcEvent::ForkRollback, Reason::Forked, req, data).await;
+        }
+    }
+
+    if RpcEvent::ClusterHalt.applies(req, data) {
+        return inject(&RpcEvent::ClusterHalt, Reason::Halted, req, data).await;
+    }
 
-    if rng.gen::<f32>() >= data.percentages.rpc_success {
-        return RpcEvent::random().respond(&payload, &data).await;
+    if data.restart_stage().is_some() {
+        return inject(&RpcEvent::ValidatorRestart, Reason::Restarting, req, data).await;
     }
 
-    match method {
+    // Signatures chosen to fail are reported consistently by every status query.
+    if req.queried_signatures().iter().any(|s| data.is_failed(s)) {
+        let event = &RpcEvent::FailedTransaction;
+        return inject(event, Reason::FailedSignature, req, data).await;
+    }
+
+    // Forked signatures and the accounts that they changed are rolled back
+    // consistently by every status query and read at processed commitment.
+    let forked = req
+        .queried_signatures()
+        .iter()
+        .any(|s| data.forks.is_forked(s, &data.fork))
+        || (req.commitment() == Some("processed") && {
+            let reverted = data.forks.reverted(&data.fork);
+            req.queried_accounts()
+                .iter()
+                .any(|a| reverted.contains_key(*a))
+        });
+    if forked {
+        return inject(&RpcEvent::ForkRollback, Reason::Forked, req, data).await;
+    }
+
+    for (index, rule) in settings.rules.iter().enumerate() {
+        if !rule.matches(req) {
+            continue;
+        }
+
+        let fault = data.faults.lookup(&rule.fault)?;
+        if !fault.applies(req, data) {
+            continue;
+        }
+
+        let draw = rng.gen::<f32>();
+        if draw < rule.rate {
+            let reason = Reason::Rule {
+                index,
+                draw,
+                threshold: rule.rate,
+            };
+            return inject(fault.as_ref(), reason, req, data).await;
+        }
+    }
+
+    let draw = rng.gen::<f32>();
+    if draw >= percentages.rpc_success {
+        let mut weights = BTreeMap::new();
+        for (name, &weight) in &settings.weights {
+            if data.faults.lookup(name)?.applies(req, data) {
+                weights.insert(name.clone(), weight);
+            }
+        }
+
+        if let Some(name) = choose_weighted(&weights, &mut rng) {
+            let fault = data.faults.lookup(&name)?;
+            let reason = Reason::RpcSuccessRate {
+                draw,
+                threshold: percentages.rpc_success,
+            };
+            return inject(fault.as_ref(), reason, req, data).await;
+        }
+    }
+
+    match req.method.as_str() {
         "getSignatureStatuses" => {
-            let param_sig = req.get("params").unwrap().as_array().unwrap()[0]
-                .as_array()
-                .unwrap()[0]
-                .as_str()
-                .unwrap()
-                .to_string();
-
-            if data.fake_signatures.read().unwrap().contains(&param_sig) {
-                RpcEvent::UnconfirmedSignature
-                    .respond(&payload, &data)
-                    .await
+            let fake = match req.queried_signatures().first() {
+                Some(sig) => data
+                    .fake_signatures
+                    .read()
+                    .unwrap()
+                    .iter()
+                    .any(|f| f == sig),
+                None => false,
+            };
+
+            if fake {
+                inject(
+                    &RpcEvent::UnconfirmedSignature,
+                    Reason::FakeSignature,
+                    req,
+                    data,
+                )
+                .await
             } else {
-                passthrough(&payload, &data).await
+                passthrough(req, data).await
             }
         }
-        "sendTransaction" if rng.gen::<f32>() >= data.percentages.tx_success => {
-            RpcEvent::FalsifiedSignature.respond(&payload, &data).await
+        "requestAirdrop" if RpcEvent::AirdropFailure.applies(req, data) => {
+            let draw = rng.gen::<f32>();
+            if draw >= percentages.airdrop_success {
+                let reason = Reason::AirdropSuccessRate {
+                    draw,
+                    threshold: percentages.airdrop_success,
+                };
+                inject(&RpcEvent::AirdropFailure, reason, req, data).await
+            } else {
+                passthrough(req, data).await
+            }
+        }
+        "sendTransaction" => {
+            let draw = rng.gen::<f32>();
+            if draw >= percentages.tx_success {
+                let reason = Reason::TxSuccessRate {
+                    draw,
+                    threshold: percentages.tx_success,
+                };
+                inject(&RpcEvent::FalsifiedSignature, reason, req, data).await
+            } else {
+                passthrough(req, data).await
+            }
         }
-        _ => passthrough(&payload, &data).await,
+        _ => passthrough(req, data).await,
     }
 }
 
+/// Records the injection of the argued fault and the reason it was chosen
+/// in the request trace and proxy metrics and returns the fault's response,
+/// which is tagged with the `X-ATC-Event` header if the fault did not set it.
+async fn inject(
+    fault: &dyn Fault,
+    reason: Reason,
+    req: &RpcRequest,
+    data: &web::Data<GlobalState>,
+) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    data.metrics
+        .events
+        .with_label_values(&[fault.name(), &req.method])
+        .inc();
+    req.decide(fault.name(), reason);
+
+    let mut res = fault.respond(req, data).await?;
+    let header = HeaderName::from_static("x-atc-event");
+    if !res.headers().contains_key(&header) {
+        res.headers_mut()
+            .insert(header, HeaderValue::from_str(fault.name())?);
+    }
+    Ok(res)
+}
+
 #[cfg(test)]
-mod tests {
+pub mod tests {
     use actix_web::http::header::{ContentType, HeaderValue};
     use actix_web::http::StatusCode;
     use actix_web::{test, web, App};
     use serde_json::{json, Value};
 
+    use async_trait::async_trait;
+
     use super::*;
-    use crate::config::PercentageSettings;
+    use crate::config::{
+        AirdropFailure, CommitmentSettings, CongestionSettings, DropSettings, FaultRule,
+        ForkSettings, PercentageSettings, ViewAssignment,
+    };
+    use crate::mock::{MockValidator, SLOT_DURATION};
+    use crate::preflight;
+    use crate::transaction::tests::wire_transaction;
+
+    /// Builds a JSON-RPC request of the argued body to the proxy.
+    pub fn rpc_request(body: Value) -> test::TestRequest {
+        test::TestRequest::post()
+            .uri("/")
+            .insert_header(ContentType::json())
+            .set_payload(body.to_string())
+    }
+
+    /// Builds the state of a proxy to a mock upstream that
+    /// only injects faults by the argued rules.
+    pub fn state_with_rules(rules: Vec<FaultRule>) -> GlobalState {
+        GlobalState {
+            fault_settings: RwLock::new(FaultSettings {
+                weights: Default::default(),
+                rules,
+            }),
+            percentages: RwLock::new(PercentageSettings::all_success()),
+            upstream: Upstream::Mock(MockValidator::default()),
+            ..Default::default()
+        }
+    }
+
+    impl PercentageSettings {
+        /// Success rates that never inject a fault by chance.
+        pub fn all_success() -> Self {
+            Self {
+                rpc_success: 1.0,
+                tx_success: 1.0,
+                airdrop_success: 1.0,
+            }
+        }
+    }
+
+    impl FaultRule {
+        /// A rule that always injects the fault into requests of the method.
+        pub fn on_method(fault: &str, method: &str) -> Self {
+            Self {
+                fault: fault.into(),
+                methods: vec![method.into()],
+                rate: 1.0,
+                ..Default::default()
+            }
+        }
+
+        /// A rule that always injects the fault into transactions of the fee payer.
+        pub fn on_fee_payer(fault: &str, payer: [u8; 32]) -> Self {
+            Self {
+                fault: fault.into(),
+                fee_payers: vec![bs58::encode(payer).into_string()],
+                rate: 1.0,
+                ..Default::default()
+            }
+        }
+    }
 
     #[actix_web::test]
     async fn health_ok() {
@@ -125,12 +529,12 @@ mod tests {
         let app = test::init_service(
             App::new()
                 .app_data(web::Data::new(GlobalState {
-                    fake_signatures: RwLock::new(Vec::new()),
-                    percentages: PercentageSettings {
+                    percentages: RwLock::new(PercentageSettings {
                         rpc_success: 1.0,
                         tx_success: 0.0,
-                    },
-                    rpc_endpoint: "".into(),
+                        airdrop_success: 1.0,
+                    }),
+                    ..Default::default()
                 }))
                 .service(rpc),
         )
@@ -164,17 +568,38 @@ mod tests {
         assert_eq!(bs58::decode(sig).into_vec().unwrap().len(), 64);
     }
 
+    #[actix_web::test]
+    async fn malformed_status_queries() {
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(state_with_rules(Vec::new())))
+                .service(rpc),
+        )
+        .await;
+
+        for params in [None, Some(json!([])), Some(json!([[]]))] {
+            let mut body = json!({"jsonrpc": "2.0", "id": 1, "method": "getSignatureStatuses"});
+            if let Some(p) = params {
+                body["params"] = p;
+            }
+            let req = rpc_request(body).to_request();
+
+            let res = test::call_service(&app, req).await;
+            assert!(res.status().is_success());
+        }
+    }
+
     #[actix_web::test]
     async fn unconfirmed_fake_signature() {
         let app = test::init_service(
             App::new()
                 .app_data(web::Data::new(GlobalState {
-                    fake_signatures: RwLock::new(Vec::new()),
-                    percentages: PercentageSettings {
+                    percentages: RwLock::new(PercentageSettings {
                         rpc_success: 1.0,
                         tx_success: 0.0,
-                    },
-                    rpc_endpoint: "".into(),
+                        airdrop_success: 1.0,
+                    }),
+                    ..Default::default()
                 }))
                 .service(rpc),
         )
@@ -226,4 +651,671 @@ mod tests {
             json!([null])
         );
     }
+
+    #[actix_web::test]
+    async fn metrics_report_injected_events() {
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(GlobalState {
+                    percentages: RwLock::new(PercentageSettings {
+                        tx_success: 0.0,
+                        ..PercentageSettings::all_success()
+                    }),
+                    ..Default::default()
+                }))
+                .service(metrics)
+                .service(rpc),
+        )
+        .await;
+
+        let tx_req = rpc_request(json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "sendTransaction",
+            "params": [""]
+        }))
+        .to_request();
+
+        test::call_service(&app, tx_req).await;
+
+        let res =
+            test::call_service(&app, test::TestRequest::get().uri("/metrics").to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let body = String::from_utf8(test::read_body(res).await.to_vec()).unwrap();
+
+        assert!(body.contains("atc_requests_total{method=\"sendTransaction\"} 1"));
+        assert!(body.contains(
+            "atc_events_total{event=\"FalsifiedSignature\",method=\"sendTransaction\"} 1"
+        ));
+        assert!(body.contains("atc_fake_signatures 1"));
+    }
+
+    #[actix_web::test]
+    async fn passthrough_mock_upstream() {
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(state_with_rules(Vec::new())))
+                .service(rpc),
+        )
+        .await;
+
+        let tx = bs58::encode(wire_transaction([5; 64], &[[6; 32]])).into_string();
+        let tx_req = rpc_request(json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "sendTransaction",
+            "params": [tx]
+        }))
+        .to_request();
+
+        let tx_res = test::call_service(&app, tx_req).await;
+        assert_eq!(tx_res.status(), StatusCode::OK);
+        assert!(tx_res.headers().get("X-ATC-Event").is_none());
+
+        let tx_body: Value = test::read_body_json(tx_res).await;
+        let sig = bs58::encode([5; 64]).into_string();
+        assert_eq!(tx_body["result"], sig);
+
+        let cnf_req = rpc_request(json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "getSignatureStatuses",
+            "params": [[sig]]
+        }))
+        .to_request();
+
+        let cnf_body: Value = test::call_and_read_body_json(&app, cnf_req).await;
+        assert_eq!(cnf_body["id"], 2);
+        assert_eq!(cnf_body["result"]["value"][0]["err"], Value::Null);
+    }
+
+    #[actix_web::test]
+    async fn custom_fault_rule() {
+        struct Unavailable;
+
+        #[async_trait(?Send)]
+        impl Fault for Unavailable {
+            fn name(&self) -> &str {
+                "Unavailable"
+            }
+
+            async fn respond(
+                &self,
+                _req: &RpcRequest,
+                _data: &web::Data<GlobalState>,
+            ) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+                Ok(HttpResponse::ServiceUnavailable().finish())
+            }
+        }
+
+        let state = state_with_rules(vec![FaultRule::on_method("Unavailable", "getSlot")]);
+        state.faults.register(Unavailable);
+
+        let app = test::init_service(App::new().app_data(web::Data::new(state)).service(rpc)).await;
+
+        let slot_res = test::call_service(
+            &app,
+            rpc_request(json!({"jsonrpc": "2.0", "id": 1, "method": "getSlot"})).to_request(),
+        )
+        .await;
+
+        assert_eq!(slot_res.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(
+            slot_res.headers().get("X-ATC-Event"),
+            Some(&HeaderValue::from_str("Unavailable").unwrap())
+        );
+
+        let health_res = test::call_service(
+            &app,
+            rpc_request(json!({"jsonrpc": "2.0", "id": 1, "method": "getHealth"})).to_request(),
+        )
+        .await;
+
+        assert_eq!(health_res.status(), StatusCode::OK);
+        assert!(health_res.headers().get("X-ATC-Event").is_none());
+    }
+
+    #[actix_web::test]
+    async fn mutated_passthrough() {
+        let state = state_with_rules(vec![
+            FaultRule::on_method("DropFields", "getLatestBlockhash"),
+            FaultRule::on_method("NullValues", "getBalance"),
+        ]);
+
+        let app = test::init_service(App::new().app_data(web::Data::new(state)).service(rpc)).await;
+
+        let bh_res = test::call_service(
+            &app,
+            rpc_request(json!({"jsonrpc": "2.0", "id": 3, "method": "getLatestBlockhash"}))
+                .to_request(),
+        )
+        .await;
+
+        assert_eq!(
+            bh_res.headers().get("X-ATC-Event"),
+            Some(&HeaderValue::from_str("DropFields").unwrap())
+        );
+
+        let bh_body: Value = test::read_body_json(bh_res).await;
+        assert_eq!(bh_body["id"], 3);
+        assert!(bh_body["result"]["value"]["blockhash"].is_string());
+        assert!(bh_body["result"]["value"]
+            .get("lastValidBlockHeight")
+            .is_none());
+
+        let bal_res = test::call_service(
+            &app,
+            rpc_request(json!({"jsonrpc": "2.0", "id": 4, "method": "getBalance", "params": ["11111111111111111111111111111111"]}))
+                .to_request(),
+        )
+        .await;
+
+        let bal_body: Value = test::read_body_json(bal_res).await;
+        assert_eq!(bal_body["result"]["value"], Value::Null);
+        assert!(bal_body["result"]["context"]["slot"].is_u64());
+    }
+
+    #[actix_web::test]
+    async fn dropped_and_late_transactions() {
+        let state = web::Data::new(GlobalState {
+            drops: DropSettings {
+                expiry_ms: 60_000,
+                landing_delay_ms: 100,
+            },
+            ..state_with_rules(vec![
+                FaultRule::on_fee_payer("DroppedTransaction", [1; 32]),
+                FaultRule::on_fee_payer("LateTransaction", [2; 32]),
+            ])
+        });
+
+        let app = test::init_service(App::new().app_data(state.clone()).service(rpc)).await;
+
+        let send = |payer: [u8; 32]| {
+            let tx = bs58::encode(wire_transaction([payer[0]; 64], &[payer])).into_string();
+            rpc_request(
+                json!({"jsonrpc": "2.0", "id": 1, "method": "sendTransaction", "params": [tx]}),
+            )
+            .to_request()
+        };
+        let status = |sig: &str| {
+            rpc_request(json!({"jsonrpc": "2.0", "id": 2, "method": "getSignatureStatuses", "params": [[sig]]}))
+                .to_request()
+        };
+
+        let dropped_sig = bs58::encode([1; 64]).into_string();
+        let late_sig = bs58::encode([2; 64]).into_string();
+
+        // The blockhash of the sent transactions expires three blocks from now.
+        let height = current_slot(&state).await;
+        state.blockhashes.observe(SeenBlockhash {
+            slot: height,
+            blockhash: bs58::encode([9; 32]).into_string(),
+            last_valid_block_height: height + 3,
+        });
+
+        for _ in 0..2 {
+            let res: Value = test::call_and_read_body_json(&app, send([1; 32])).await;
+            assert_eq!(res["result"], dropped_sig);
+        }
+        let res: Value = test::call_and_read_body_json(&app, send([2; 32])).await;
+        assert_eq!(res["result"], late_sig);
+
+        assert!(state.is_dropped(&dropped_sig));
+        assert_eq!(
+            state.event_log.history(&Default::default())[1].reason,
+            Reason::DroppedSignature
+        );
+
+        let res: Value = test::call_and_read_body_json(&app, status(&late_sig)).await;
+        assert_eq!(res["result"]["value"][0], Value::Null);
+
+        tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+
+        let res: Value = test::call_and_read_body_json(&app, status(&late_sig)).await;
+        assert!(res["result"]["value"][0]["confirmationStatus"].is_string());
+        let res: Value = test::call_and_read_body_json(&app, status(&dropped_sig)).await;
+        assert_eq!(res["result"]["value"][0], Value::Null);
+        assert!(state.is_dropped(&dropped_sig));
+        assert!(!state.is_dropped(&late_sig));
+
+        tokio::time::sleep(SLOT_DURATION * 3).await;
+        assert!(!state.is_dropped(&dropped_sig));
+    }
+
+    #[actix_web::test]
+    async fn spoofed_transaction_failure() {
+        let state = web::Data::new(state_with_rules(vec![FaultRule {
+            fault: "FailedTransaction".into(),
+            methods: vec!["sendTransaction".into()],
+            fee_payers: vec![bs58::encode([1; 32]).into_string()],
+            rate: 1.0,
+            ..Default::default()
+        }]));
+
+        let app = test::init_service(App::new().app_data(state.clone()).service(rpc)).await;
+
+        let mut sigs = Vec::new();
+        for payer in [[1; 32], [2; 32]] {
+            let tx = bs58::encode(wire_transaction([payer[0]; 64], &[payer])).into_string();
+            let res: Value = test::call_and_read_body_json(
+                &app,
+                rpc_request(
+                    json!({"jsonrpc": "2.0", "id": 1, "method": "sendTransaction", "params": [tx]}),
+                )
+                .to_request(),
+            )
+            .await;
+            sigs.push(res["result"].as_str().unwrap().to_string());
+        }
+
+        let res = test::call_service(
+            &app,
+            rpc_request(json!({"jsonrpc": "2.0", "id": 2, "method": "getSignatureStatuses", "params": [sigs]})).to_request(),
+        )
+        .await;
+        assert_eq!(
+            res.headers().get("X-ATC-Event"),
+            Some(&HeaderValue::from_str("FailedTransaction").unwrap())
+        );
+
+        let body: Value = test::read_body_json(res).await;
+        let statuses = &body["result"]["value"];
+        assert_eq!(statuses[0]["err"], state.failures.err);
+        assert!(statuses[0]["confirmationStatus"].is_string());
+        assert_eq!(statuses[1]["err"], Value::Null);
+
+        // Failures expire with the blockhash that the transaction was sent with.
+        let sent = Instant::now() - SEEN_RETENTION;
+        state
+            .failed_signatures
+            .write()
+            .unwrap()
+            .insert(sigs[0].clone(), sent);
+        assert!(!state.is_failed(&sigs[0]));
+
+        let res = test::call_service(
+            &app,
+            rpc_request(
+                json!({"jsonrpc": "2.0", "id": 3, "method": "getTransaction", "params": [sigs[0]]}),
+            )
+            .to_request(),
+        )
+        .await;
+        assert!(res.headers().get("X-ATC-Event").is_none());
+    }
+
+    #[actix_web::test]
+    async fn congested_fee_market() {
+        let state = web::Data::new(GlobalState {
+            congestion: CongestionSettings {
+                miss_rate: 1.0,
+                late_rate: 0.0,
+                ..Default::default()
+            },
+            ..state_with_rules(vec![FaultRule {
+                fault: "Congestion".into(),
+                methods: vec![
+                    "getRecentPrioritizationFees".into(),
+                    "sendTransaction".into(),
+                ],
+                rate: 1.0,
+                ..Default::default()
+            }])
+        });
+
+        let app = test::init_service(App::new().app_data(state.clone()).service(rpc)).await;
+
+        let fees: Value = test::call_and_read_body_json(
+            &app,
+            rpc_request(json!({"jsonrpc": "2.0", "id": 1, "method": "getRecentPrioritizationFees", "params": [[]]})).to_request(),
+        )
+        .await;
+        assert!(fees["result"][0]["prioritizationFee"].as_u64().unwrap() > 0);
+
+        let tx = bs58::encode(wire_transaction([4; 64], &[[4; 32]])).into_string();
+        let res = test::call_service(
+            &app,
+            rpc_request(
+                json!({"jsonrpc": "2.0", "id": 2, "method": "sendTransaction", "params": [tx]}),
+            )
+            .to_request(),
+        )
+        .await;
+        assert_eq!(
+            res.headers().get("X-ATC-Event"),
+            Some(&HeaderValue::from_str("DroppedTransaction").unwrap())
+        );
+        assert!(state.is_dropped(&bs58::encode([4; 64]).into_string()));
+    }
+
+    #[actix_web::test]
+    async fn preflight_failure() {
+        let state = web::Data::new(state_with_rules(vec![FaultRule {
+            fault: "PreflightFailure".into(),
+            methods: vec!["getSlot".into(), "sendTransaction".into()],
+            rate: 1.0,
+            ..Default::default()
+        }]));
+
+        let app = test::init_service(App::new().app_data(state.clone()).service(rpc)).await;
+
+        let slot = test::call_service(
+            &app,
+            rpc_request(json!({"jsonrpc": "2.0", "id": 1, "method": "getSlot"})).to_request(),
+        )
+        .await;
+        assert!(slot.headers().get("X-ATC-Event").is_none());
+        let slot: Value = test::read_body_json(slot).await;
+        assert!(slot["result"].is_u64());
+
+        let tx = bs58::encode(wire_transaction([4; 64], &[[4; 32]])).into_string();
+        let sent: Value = test::call_and_read_body_json(
+            &app,
+            rpc_request(
+                json!({"jsonrpc": "2.0", "id": 2, "method": "sendTransaction", "params": [tx]}),
+            )
+            .to_request(),
+        )
+        .await;
+        assert_eq!(sent["error"]["code"], preflight::SIMULATION_FAILED);
+
+        let events = state.event_log.history(&Default::default());
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].method, "sendTransaction");
+    }
+
+    #[actix_web::test]
+    async fn stale_blockhash() {
+        let state = web::Data::new(state_with_rules(vec![FaultRule {
+            fault: "StaleBlockhash".into(),
+            methods: vec!["getLatestBlockhash".into(), "isBlockhashValid".into()],
+            rate: 1.0,
+            ..Default::default()
+        }]));
+
+        let app = test::init_service(App::new().app_data(state.clone()).service(rpc)).await;
+
+        let bh: Value = test::call_and_read_body_json(
+            &app,
+            rpc_request(json!({"jsonrpc": "2.0", "id": 1, "method": "getLatestBlockhash"}))
+                .to_request(),
+        )
+        .await;
+        let slot = bh["result"]["context"]["slot"].as_u64().unwrap();
+        let last_valid = bh["result"]["value"]["lastValidBlockHeight"]
+            .as_u64()
+            .unwrap();
+        assert!(last_valid <= slot);
+        assert!(state.blockhashes.older_than(slot, 0).is_some());
+
+        let latest = MockValidator::blockhash(slot);
+        let valid: Value = test::call_and_read_body_json(
+            &app,
+            rpc_request(json!({"jsonrpc": "2.0", "id": 2, "method": "isBlockhashValid", "params": [latest]})).to_request(),
+        )
+        .await;
+        assert_eq!(valid["result"]["value"], false);
+    }
+
+    #[actix_web::test]
+    async fn inconsistent_reads() {
+        let state = web::Data::new(GlobalState {
+            views: ViewSettings {
+                lags_ms: vec![0, 4_000],
+                assignment: ViewAssignment::RoundRobin,
+            },
+            ..state_with_rules(vec![FaultRule {
+                fault: "InconsistentReads".into(),
+                rate: 1.0,
+                ..Default::default()
+            }])
+        });
+
+        let app = test::init_service(App::new().app_data(state.clone()).service(rpc)).await;
+
+        let tx = bs58::encode(wire_transaction([7; 64], &[[1; 32]])).into_string();
+        let sent: Value = test::call_and_read_body_json(
+            &app,
+            rpc_request(
+                json!({"jsonrpc": "2.0", "id": 1, "method": "sendTransaction", "params": [tx]}),
+            )
+            .to_request(),
+        )
+        .await;
+        let sig = sent["result"].as_str().unwrap().to_string();
+        let statuses =
+            json!({"jsonrpc": "2.0", "id": 2, "method": "getSignatureStatuses", "params": [[sig]]});
+
+        let lagging = test::call_service(&app, rpc_request(statuses.clone()).to_request()).await;
+        assert_eq!(lagging.headers().get("X-ATC-View").unwrap(), "1");
+        let lagging: Value = test::read_body_json(lagging).await;
+        assert_eq!(lagging["result"]["value"][0], Value::Null);
+
+        let current: Value =
+            test::call_and_read_body_json(&app, rpc_request(statuses.clone()).to_request()).await;
+        assert!(current["result"]["value"][0].is_object());
+
+        let slot = json!({"jsonrpc": "2.0", "id": 3, "method": "getSlot"});
+        let behind: Value =
+            test::call_and_read_body_json(&app, rpc_request(slot.clone()).to_request()).await;
+        let ahead: Value =
+            test::call_and_read_body_json(&app, rpc_request(slot).to_request()).await;
+        let (behind, ahead) = (
+            behind["result"].as_u64().unwrap(),
+            ahead["result"].as_u64().unwrap(),
+        );
+        assert!(behind < ahead && behind <= ahead.saturating_sub(10));
+    }
+
+    #[actix_web::test]
+    async fn forked_transaction_rollback() {
+        let state = web::Data::new(GlobalState {
+            fork: ForkSettings {
+                rollback_rate: 1.0,
+                rollback_ms: 300,
+            },
+            ..state_with_rules(vec![FaultRule {
+                fault: "ForkRollback".into(),
+                methods: vec!["sendTransaction".into(), "simulateTransaction".into()],
+                rate: 1.0,
+                ..Default::default()
+            }])
+        });
+
+        let app = test::init_service(App::new().app_data(state.clone()).service(rpc)).await;
+        let call = |body: &Value| rpc_request(body.clone()).to_request();
+
+        let payer = bs58::encode([4; 32]).into_string();
+        let balance = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "getBalance",
+            "params": [payer, {"commitment": "processed"}]
+        });
+        let account = json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "getAccountInfo",
+            "params": [payer, {"commitment": "processed", "encoding": "base64"}]
+        });
+        let before: Value = test::call_and_read_body_json(&app, call(&balance)).await;
+        let before = before["result"]["value"].as_u64().unwrap();
+
+        let tx = bs58::encode(wire_transaction([4; 64], &[[4; 32]])).into_string();
+        let sent = test::call_service(
+            &app,
+            call(&json!({"jsonrpc": "2.0", "id": 3, "method": "sendTransaction", "params": [tx]})),
+        )
+        .await;
+        assert_eq!(sent.headers().get("X-ATC-Event").unwrap(), "ForkRollback");
+        let sent: Value = test::read_body_json(sent).await;
+        let sig = sent["result"].as_str().unwrap().to_string();
+        let statuses =
+            json!({"jsonrpc": "2.0", "id": 4, "method": "getSignatureStatuses", "params": [[sig]]});
+
+        // Simulations of the forked transaction are not answered like a retry.
+        let simulated = test::call_service(
+            &app,
+            call(&json!({"jsonrpc": "2.0", "id": 5, "method": "simulateTransaction", "params": [tx]})),
+        )
+        .await;
+        assert!(simulated.headers().get("X-ATC-Event").is_none());
+        let simulated: Value = test::read_body_json(simulated).await;
+        assert_ne!(simulated["result"], sig);
+
+        // The transaction is on the fork, so its fee is charged at processed commitment.
+        let processed = test::call_service(&app, call(&statuses)).await;
+        assert_eq!(
+            processed.headers().get("X-ATC-Event").unwrap(),
+            "ForkRollback"
+        );
+        let processed: Value = test::read_body_json(processed).await;
+        let status = &processed["result"]["value"][0];
+        assert_eq!(status["confirmationStatus"], "processed");
+        let charged: Value = test::call_and_read_body_json(&app, call(&balance)).await;
+        let charged = charged["result"]["value"].as_u64().unwrap();
+        assert!(charged < before);
+
+        // Once the fork is abandoned, the transaction and its fee are rolled back.
+        tokio::time::sleep(std::time::Duration::from_millis(350)).await;
+        let rolled_back: Value = test::call_and_read_body_json(&app, call(&statuses)).await;
+        assert_eq!(rolled_back["result"]["value"][0], Value::Null);
+        let reverted = test::call_service(&app, call(&balance)).await;
+        assert_eq!(
+            reverted.headers().get("X-ATC-Event").unwrap(),
+            "ForkRollback"
+        );
+        let reverted: Value = test::read_body_json(reverted).await;
+        assert_eq!(reverted["result"]["value"], before);
+        let reverted: Value = test::call_and_read_body_json(&app, call(&account)).await;
+        assert_eq!(reverted["result"]["value"]["lamports"], before);
+
+        // The transaction then lands again on the surviving fork.
+        tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+        let landed: Value = test::call_and_read_body_json(&app, call(&statuses)).await;
+        assert!(landed["result"]["value"][0]["confirmationStatus"].is_string());
+        let after: Value = test::call_and_read_body_json(&app, call(&balance)).await;
+        assert_eq!(after["result"]["value"], charged);
+    }
+
+    #[actix_web::test]
+    async fn airdrop_failures() {
+        let payer = bs58::encode([9; 32]).into_string();
+        let airdrop = json!({"jsonrpc": "2.0", "id": 1, "method": "requestAirdrop", "params": [payer, 1_000]});
+        let balance = json!({"jsonrpc": "2.0", "id": 2, "method": "getBalance", "params": [payer]});
+
+        for failure in AirdropSettings::default().failures {
+            let state = web::Data::new(GlobalState {
+                airdrop: AirdropSettings {
+                    failures: vec![failure],
+                    partial_fraction: 0.25,
+                },
+                percentages: RwLock::new(PercentageSettings {
+                    airdrop_success: 0.0,
+                    ..PercentageSettings::all_success()
+                }),
+                ..state_with_rules(Vec::new())
+            });
+            let app = test::init_service(App::new().app_data(state.clone()).service(rpc)).await;
+
+            let before: Value =
+                test::call_and_read_body_json(&app, rpc_request(balance.clone()).to_request())
+                    .await;
+            let res = test::call_service(&app, rpc_request(airdrop.clone()).to_request()).await;
+            assert_eq!(res.headers().get("X-ATC-Event").unwrap(), "AirdropFailure");
+            let status = res.status();
+            let body: Value = test::read_body_json(res).await;
+            let after: Value =
+                test::call_and_read_body_json(&app, rpc_request(balance.clone()).to_request())
+                    .await;
+            let received = after["result"]["value"].as_u64().unwrap()
+                - before["result"]["value"].as_u64().unwrap();
+
+            match failure {
+                AirdropFailure::RateLimit => {
+                    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+                    assert!(body["error"]["message"]
+                        .as_str()
+                        .unwrap()
+                        .contains("airdrop limit"));
+                }
+                AirdropFailure::LimitReached => {
+                    assert_eq!(body["error"]["message"], "airdrop request limit reached");
+                }
+                AirdropFailure::NeverLands => {
+                    let sig = body["result"].as_str().unwrap();
+                    let statuses: Value = test::call_and_read_body_json(
+                        &app,
+                        rpc_request(json!({"jsonrpc": "2.0", "id": 3, "method": "getSignatureStatuses", "params": [[sig]]})).to_request(),
+                    )
+                    .await;
+                    assert_eq!(statuses["result"]["value"][0], Value::Null);
+                }
+                AirdropFailure::Partial => {
+                    assert!(body["result"].is_string());
+                    assert_eq!(received, 250);
+                    let events = state.event_log.history(&Default::default());
+                    assert_eq!(events[0].upstream_status, Some(200));
+                    continue;
+                }
+            }
+            assert_eq!(received, 0);
+        }
+    }
+
+    #[actix_web::test]
+    async fn released_commitment_finalizes() {
+        let state = web::Data::new(GlobalState {
+            commitment: CommitmentSettings {
+                processed_ms: 100,
+                confirmed_ms: 100,
+            },
+            ..state_with_rules(vec![FaultRule::on_method(
+                "CommitmentLag",
+                "getSignatureStatuses",
+            )])
+        });
+
+        let app = test::init_service(App::new().app_data(state.clone()).service(rpc)).await;
+
+        let tx = bs58::encode(wire_transaction([3; 64], &[[3; 32]])).into_string();
+        let sent: Value = test::call_and_read_body_json(
+            &app,
+            rpc_request(
+                json!({"jsonrpc": "2.0", "id": 1, "method": "sendTransaction", "params": [tx]}),
+            )
+            .to_request(),
+        )
+        .await;
+        let statuses = json!({"jsonrpc": "2.0", "id": 2, "method": "getSignatureStatuses", "params": [[sent["result"]]]});
+
+        let first: Value =
+            test::call_and_read_body_json(&app, rpc_request(statuses.clone()).to_request()).await;
+        let first_seen = std::time::Instant::now();
+        assert_eq!(
+            first["result"]["value"][0]["confirmationStatus"],
+            "processed"
+        );
+
+        // Polls past the full lifetime until the upstream finalizes the signature,
+        // which must never be held back at processed again once released.
+        let levels = ["processed", "confirmed", "finalized"];
+        let mut reached = 0;
+        while levels[reached] != "finalized" {
+            tokio::time::sleep(std::time::Duration::from_millis(150)).await;
+            let res: Value =
+                test::call_and_read_body_json(&app, rpc_request(statuses.clone()).to_request())
+                    .await;
+            let level = res["result"]["value"][0]["confirmationStatus"].clone();
+            let rank = levels.iter().position(|l| level == *l).unwrap();
+            assert!(
+                rank >= reached,
+                "regressed to {level} after {}",
+                levels[reached]
+            );
+            reached = rank;
+            assert!(first_seen.elapsed() < std::time::Duration::from_secs(20));
+        }
+    }
 }
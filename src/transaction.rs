@@ -0,0 +1,307 @@
+// Copyright (C) 2022 JET PROTOCOL HOLDINGS, LLC.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use serde_json::Value;
+
+/// Prefix bit of the first message byte that marks a versioned message.
+const VERSION_PREFIX: u8 = 0x80;
+
+/// Program whose instructions set the compute budget of a transaction.
+pub const COMPUTE_BUDGET_PROGRAM: &str = "ComputeBudget111111111111111111111111111111";
+
+/// Instruction discriminator of `ComputeBudgetInstruction::SetComputeUnitLimit`.
+const SET_COMPUTE_UNIT_LIMIT: u8 = 2;
+
+/// Instruction discriminator of `ComputeBudgetInstruction::SetComputeUnitPrice`.
+const SET_COMPUTE_UNIT_PRICE: u8 = 3;
+
+/// Details decoded from a serialized Solana wire transaction.
+#[derive(Clone, Debug)]
+#[cfg_attr(test, derive(PartialEq))]
+pub struct Transaction {
+    pub signatures: Vec<String>,
+    pub account_keys: Vec<String>,
+    pub recent_blockhash: String,
+    pub instructions: Vec<Instruction>,
+}
+
+/// A compiled instruction of a decoded transaction with its program resolved.
+#[derive(Clone, Debug)]
+#[cfg_attr(test, derive(PartialEq))]
+pub struct Instruction {
+    pub program_id: String,
+    pub accounts: Vec<u8>,
+    pub data: Vec<u8>,
+}
+
+impl Transaction {
+    /// Decodes the transaction from the parameters of a `sendTransaction`
+    /// request, respecting the `encoding` of its optional config object.
+    pub fn from_params(params: &[Value]) -> Option<Self> {
+        let encoded = params.first()?.as_str()?;
+        let encoding = params
+            .get(1)
+            .and_then(|c| c.get("encoding"))
+            .and_then(Value::as_str)
+            .unwrap_or("base58");
+
+        let bytes = match encoding {
+            "base64" => base64::decode(encoded).ok()?,
+            _ => bs58::decode(encoded).into_vec().ok()?,
+        };
+
+        Self::decode(&bytes)
+    }
+
+    /// Decodes the transaction from its serialized wire format bytes.
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let mut r = Reader { bytes, pos: 0 };
+
+        let num_sigs = r.short_vec()?;
+        let signatures = (0..num_sigs)
+            .map(|_| r.take(64).map(|s| bs58::encode(s).into_string()))
+            .collect::<Option<Vec<_>>>()?;
+
+        if r.peek()? & VERSION_PREFIX != 0 {
+            r.take(1)?;
+        }
+
+        r.take(3)?;
+
+        let num_keys = r.short_vec()?;
+        let account_keys = (0..num_keys)
+            .map(|_| r.take(32).map(|k| bs58::encode(k).into_string()))
+            .collect::<Option<Vec<_>>>()?;
+
+        let recent_blockhash = bs58::encode(r.take(32)?).into_string();
+
+        let num_ixs = r.short_vec()?;
+        let instructions = (0..num_ixs)
+            .map(|_| {
+                let program_id = account_keys.get(r.take(1)?[0] as usize)?.clone();
+                let num_accounts = r.short_vec()?;
+                let accounts = r.take(num_accounts)?.to_vec();
+                let data_len = r.short_vec()?;
+                let data = r.take(data_len)?.to_vec();
+                Some(Instruction {
+                    program_id,
+                    accounts,
+                    data,
+                })
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        if signatures.is_empty() || account_keys.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            signatures,
+            account_keys,
+            recent_blockhash,
+            instructions,
+        })
+    }
+
+    /// Returns the first signature of the transaction, which is its identifier.
+    pub fn signature(&self) -> &str {
+        &self.signatures[0]
+    }
+
+    /// Returns the account that pays the transaction fees.
+    pub fn fee_payer(&self) -> &str {
+        &self.account_keys[0]
+    }
+
+    /// Returns the distinct programs invoked by the transaction's instructions.
+    pub fn program_ids(&self) -> Vec<&str> {
+        let mut ids: Vec<&str> = Vec::new();
+        for ix in &self.instructions {
+            if !ids.contains(&ix.program_id.as_str()) {
+                ids.push(&ix.program_id);
+            }
+        }
+        ids
+    }
+
+    /// Returns the compute unit limit requested by the transaction's compute budget instructions.
+    pub fn compute_unit_limit(&self) -> Option<u32> {
+        self.compute_budget(SET_COMPUTE_UNIT_LIMIT)
+            .and_then(|d| Some(u32::from_le_bytes(d.get(..4)?.try_into().ok()?)))
+    }
+
+    /// Returns the compute unit price in micro-lamports set by the
+    /// transaction's compute budget instructions.
+    pub fn compute_unit_price(&self) -> Option<u64> {
+        self.compute_budget(SET_COMPUTE_UNIT_PRICE)
+            .and_then(|d| Some(u64::from_le_bytes(d.get(..8)?.try_into().ok()?)))
+    }
+
+    /// Returns the data following the discriminator of the last compute
+    /// budget instruction of the argued kind.
+    fn compute_budget(&self, discriminator: u8) -> Option<&[u8]> {
+        self.instructions
+            .iter()
+            .rev()
+            .filter(|ix| ix.program_id == COMPUTE_BUDGET_PROGRAM)
+            .find(|ix| ix.data.first() == Some(&discriminator))
+            .map(|ix| &ix.data[1..])
+    }
+
+    /// Returns whether the transaction invokes the argued program.
+    pub fn invokes(&self, program_id: &str) -> bool {
+        self.instructions
+            .iter()
+            .any(|ix| ix.program_id == program_id)
+    }
+}
+
+/// Cursor over the bytes of a serialized transaction.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        let s = self.bytes.get(self.pos..self.pos + n)?;
+        self.pos += n;
+        Some(s)
+    }
+
+    /// Reads a compact-u16 length prefix.
+    fn short_vec(&mut self) -> Option<usize> {
+        let mut len = 0usize;
+        for i in 0..3 {
+            let b = self.take(1)?[0];
+            len |= ((b & 0x7f) as usize) << (i * 7);
+            if b & 0x80 == 0 {
+                return Some(len);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    /// Builds the wire bytes of a legacy transaction with the argued signature
+    /// and account keys, a fixed blockhash and no instructions.
+    pub fn wire_transaction(sig: [u8; 64], keys: &[[u8; 32]]) -> Vec<u8> {
+        wire_transaction_with(sig, keys, &[])
+    }
+
+    /// Builds the wire bytes of a legacy transaction like `wire_transaction`
+    /// with instructions of a program key index and data, and no accounts.
+    pub fn wire_transaction_with(
+        sig: [u8; 64],
+        keys: &[[u8; 32]],
+        instructions: &[(u8, &[u8])],
+    ) -> Vec<u8> {
+        let mut bytes = vec![1];
+        bytes.extend_from_slice(&sig);
+        bytes.extend_from_slice(&[1, 0, 0]);
+        bytes.push(keys.len() as u8);
+        for k in keys {
+            bytes.extend_from_slice(k);
+        }
+        bytes.extend_from_slice(&[9; 32]);
+        bytes.push(instructions.len() as u8);
+        for (program, data) in instructions {
+            bytes.extend_from_slice(&[*program, 0, data.len() as u8]);
+            bytes.extend_from_slice(data);
+        }
+        bytes
+    }
+
+    #[test]
+    fn decode_legacy() {
+        let bytes = wire_transaction([7; 64], &[[1; 32], [2; 32]]);
+        let tx = Transaction::decode(&bytes).unwrap();
+
+        assert_eq!(tx.signature(), bs58::encode([7; 64]).into_string());
+        assert_eq!(tx.fee_payer(), bs58::encode([1; 32]).into_string());
+        assert_eq!(tx.account_keys.len(), 2);
+        assert_eq!(tx.recent_blockhash, bs58::encode([9; 32]).into_string());
+        assert!(tx.program_ids().is_empty());
+    }
+
+    #[test]
+    fn decode_instructions() {
+        let bytes = wire_transaction_with(
+            [7; 64],
+            &[[1; 32], [2; 32], [3; 32]],
+            &[(2, &[1, 2]), (1, &[]), (2, &[3])],
+        );
+        let tx = Transaction::decode(&bytes).unwrap();
+
+        let program = bs58::encode([3; 32]).into_string();
+        assert_eq!(tx.instructions.len(), 3);
+        assert_eq!(tx.instructions[0].data, vec![1, 2]);
+        assert_eq!(
+            tx.program_ids(),
+            vec![program.as_str(), &bs58::encode([2; 32]).into_string()]
+        );
+        assert!(tx.invokes(&program));
+        assert!(!tx.invokes(tx.fee_payer()));
+
+        assert_eq!(tx.compute_unit_price(), None);
+
+        let out_of_range = wire_transaction_with([7; 64], &[[1; 32]], &[(4, &[])]);
+        assert!(Transaction::decode(&out_of_range).is_none());
+    }
+
+    #[test]
+    fn decode_params_encodings() {
+        let bytes = wire_transaction([3; 64], &[[4; 32]]);
+        let b58 = bs58::encode(&bytes).into_string();
+        let b64 = base64::encode(&bytes);
+
+        let from_b58 = Transaction::from_params(&[json!(b58)]).unwrap();
+        let from_b64 =
+            Transaction::from_params(&[json!(b64), json!({"encoding": "base64"})]).unwrap();
+
+        assert_eq!(from_b58, from_b64);
+        assert!(Transaction::from_params(&[json!("")]).is_none());
+        assert!(Transaction::from_params(&[]).is_none());
+    }
+
+    #[test]
+    fn decode_compute_budget() {
+        let budget = bs58::decode(COMPUTE_BUDGET_PROGRAM).into_vec().unwrap();
+        let mut limit = vec![2];
+        limit.extend_from_slice(&300_000u32.to_le_bytes());
+        let mut price = vec![3];
+        price.extend_from_slice(&25_000u64.to_le_bytes());
+
+        let bytes = wire_transaction_with(
+            [1; 64],
+            &[[2; 32], budget.try_into().unwrap()],
+            &[(1, &limit), (1, &price)],
+        );
+        let tx = Transaction::decode(&bytes).unwrap();
+
+        assert_eq!(tx.compute_unit_limit(), Some(300_000));
+        assert_eq!(tx.compute_unit_price(), Some(25_000));
+    }
+}
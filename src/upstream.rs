@@ -0,0 +1,172 @@
+// Copyright (C) 2022 JET PROTOCOL HOLDINGS, LLC.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use actix_web::http::StatusCode;
+use actix_web::web;
+
+use crate::cassette::Cassette;
+use crate::config::{CassetteMode, Config};
+use crate::mock::MockValidator;
+use crate::request::RpcRequest;
+
+/// The source of non-manipulated RPC responses that the proxy is fronting.
+pub enum Upstream {
+    /// A live RPC endpoint reached over HTTP.
+    Http {
+        client: reqwest::Client,
+        endpoint: String,
+    },
+    /// An in-process mock validator.
+    Mock(MockValidator),
+    /// Another upstream whose responses are recorded to a cassette.
+    Record {
+        cassette: Cassette,
+        inner: Box<Upstream>,
+    },
+    /// Responses replayed from a previously recorded cassette.
+    Replay(Cassette),
+}
+
+impl Upstream {
+    /// Creates a new HTTP upstream for the argued RPC endpoint URL.
+    pub fn http(endpoint: impl Into<String>) -> Self {
+        Self::Http {
+            client: reqwest::Client::new(),
+            endpoint: endpoint.into(),
+        }
+    }
+
+    /// Sends the argued request to the upstream and returns
+    /// the status code and body of its response.
+    pub async fn call(
+        &self,
+        req: &RpcRequest,
+    ) -> Result<(StatusCode, web::Bytes), Box<dyn std::error::Error>> {
+        match self {
+            Upstream::Http { client, endpoint } => {
+                send(client, endpoint, req.payload.clone()).await
+            }
+            Upstream::Mock(mock) => mock.call(req).await,
+            Upstream::Record { cassette, inner } => {
+                let (status, body) = Box::pin(inner.call(req)).await?;
+                cassette.record(req, status, &body)?;
+                Ok((status, body))
+            }
+            Upstream::Replay(cassette) => cassette.replay(req).ok_or_else(|| {
+                format!("no recorded response in cassette for method {}", req.method).into()
+            }),
+        }
+    }
+
+    /// Sends a request that the proxy makes on its own behalf to the live upstream
+    /// without recording it to a cassette, so that recordings only hold the traffic
+    /// of clients. Returns `None` when replaying, as there is no live upstream.
+    pub async fn probe(
+        &self,
+        req: &RpcRequest,
+    ) -> Result<Option<(StatusCode, web::Bytes)>, Box<dyn std::error::Error>> {
+        match self {
+            Upstream::Record { inner, .. } => Box::pin(inner.probe(req)).await,
+            Upstream::Replay(_) => Ok(None),
+            _ => self.call(req).await.map(Some),
+        }
+    }
+}
+
+impl Default for Upstream {
+    fn default() -> Self {
+        Self::http("")
+    }
+}
+
+impl TryFrom<&Config> for Upstream {
+    type Error = std::io::Error;
+
+    fn try_from(c: &Config) -> Result<Self, Self::Error> {
+        let live = if c.mock_upstream {
+            Self::Mock(MockValidator::default())
+        } else {
+            Self::http(c.rpc_endpoint.clone())
+        };
+
+        let settings = match &c.cassette {
+            Some(s) => s,
+            None => return Ok(live),
+        };
+
+        let cassette = Cassette::try_from(settings)?;
+        Ok(match cassette.mode() {
+            CassetteMode::Record => Self::Record {
+                cassette,
+                inner: Box::new(live),
+            },
+            CassetteMode::Replay => Self::Replay(cassette),
+        })
+    }
+}
+
+/// Posts the JSON-RPC payload to the argued endpoint.
+async fn send(
+    client: &reqwest::Client,
+    endpoint: &str,
+    payload: web::Bytes,
+) -> Result<(StatusCode, web::Bytes), Box<dyn std::error::Error>> {
+    let res = client
+        .post(endpoint)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(payload)
+        .send()
+        .await?;
+
+    let status = StatusCode::from_u16(res.status().as_u16())?;
+    Ok((status, res.bytes().await?))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::config::CassetteSettings;
+
+    #[actix_rt::test]
+    async fn unrecorded_probes() {
+        let path = std::env::temp_dir().join(format!("atc-probes-{}.jsonl", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+
+        let config = |mode: CassetteMode| Config {
+            mock_upstream: true,
+            cassette: Some(CassetteSettings {
+                mode,
+                path: path.into(),
+                matching: Default::default(),
+            }),
+            ..Default::default()
+        };
+        let payload = json!({"jsonrpc": "2.0", "id": 1, "method": "getSlot"}).to_string();
+        let req = RpcRequest::parse(1, None, payload.into()).unwrap();
+
+        let recorder = Upstream::try_from(&config(CassetteMode::Record)).unwrap();
+        assert!(recorder.probe(&req).await.unwrap().is_some());
+        drop(recorder);
+
+        let replayer = Upstream::try_from(&config(CassetteMode::Replay)).unwrap();
+        assert!(replayer.probe(&req).await.unwrap().is_none());
+        assert!(replayer.call(&req).await.is_err());
+
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,197 @@
+// Copyright (C) 2022 JET PROTOCOL HOLDINGS, LLC.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use rand::Rng;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::config::{ViewAssignment, ViewSettings};
+use crate::mock::SLOT_DURATION;
+
+/// How long a signature's first sighting is remembered beyond the period
+/// that it affects, matching the lifetime of the blockhash it was sent with.
+pub const SEEN_RETENTION: Duration = Duration::from_secs(90);
+
+/// Returns the index of the simulated backend view that the next request
+/// is assigned to, using the argued counter for round-robin assignment.
+pub fn assign<R: Rng + ?Sized>(
+    settings: &ViewSettings,
+    counter: &AtomicUsize,
+    rng: &mut R,
+) -> usize {
+    let count = settings.lags_ms.len().max(1);
+    match settings.assignment {
+        ViewAssignment::Random => rng.gen_range(0..count),
+        ViewAssignment::RoundRobin => counter.fetch_add(1, Ordering::Relaxed) % count,
+    }
+}
+
+/// Returns the number of slots that the argued lag amounts to.
+pub fn lag_slots(lag: Duration) -> u64 {
+    (lag.as_millis() / SLOT_DURATION.as_millis()) as u64
+}
+
+/// Moves the slots and block heights of the result back by the argued number
+/// of slots, as a node lagging behind the upstream would report them.
+pub fn age(result: &mut Value, method: &str, slots: u64) {
+    let behind = |v: &mut Value| {
+        if let Some(n) = v.as_u64() {
+            *v = Value::from(n.saturating_sub(slots));
+        }
+    };
+
+    match method {
+        "getSlot" | "getBlockHeight" => behind(result),
+        "getEpochInfo" => {
+            for key in ["absoluteSlot", "blockHeight", "slotIndex"] {
+                if let Some(v) = result.get_mut(key) {
+                    behind(v);
+                }
+            }
+        }
+        _ => {}
+    }
+
+    if let Some(slot) = result.pointer_mut("/context/slot") {
+        behind(slot);
+    }
+    if let Some(height) = result.pointer_mut("/value/lastValidBlockHeight") {
+        behind(height);
+    }
+}
+
+/// Hides the statuses in the result of a `getSignatureStatuses` or `getTransaction`
+/// request that the upstream first reported less than the argued lag ago, recording
+/// when each of the queried signatures was first seen with a status.
+pub fn hide_unseen(
+    result: &mut Value,
+    method: &str,
+    sigs: &[&str],
+    seen: &mut HashMap<String, Instant>,
+    lag: Duration,
+) {
+    let now = Instant::now();
+    seen.retain(|_, t| now.duration_since(*t) < lag.max(SEEN_RETENTION) + SEEN_RETENTION);
+
+    let mut hide = |sig: &str, status: &mut Value| {
+        if status.is_null() {
+            return;
+        }
+        let first = *seen.entry(sig.to_string()).or_insert(now);
+        if now.duration_since(first) < lag {
+            *status = Value::Null;
+        }
+    };
+
+    match method {
+        "getSignatureStatuses" => {
+            if let Some(Value::Array(statuses)) = result.get_mut("value") {
+                sigs.iter()
+                    .zip(statuses.iter_mut())
+                    .for_each(|(sig, s)| hide(sig, s));
+            }
+        }
+        "getTransaction" => {
+            if let Some(sig) = sigs.first() {
+                hide(sig, result);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::thread_rng;
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn view_assignment() {
+        let counter = AtomicUsize::new(0);
+        let settings = ViewSettings {
+            lags_ms: vec![0, 1_000, 2_000],
+            assignment: ViewAssignment::RoundRobin,
+        };
+        let views: Vec<usize> = (0..4)
+            .map(|_| assign(&settings, &counter, &mut thread_rng()))
+            .collect();
+        assert_eq!(views, vec![0, 1, 2, 0]);
+
+        let random = ViewSettings {
+            assignment: ViewAssignment::Random,
+            ..settings
+        };
+        assert!((0..20).all(|_| assign(&random, &counter, &mut thread_rng()) < 3));
+    }
+
+    #[test]
+    fn aged_results() {
+        let mut slot = json!(1000);
+        age(&mut slot, "getSlot", 10);
+        assert_eq!(slot, 990);
+
+        let mut epoch =
+            json!({"absoluteSlot": 1000, "blockHeight": 900, "slotIndex": 5, "epoch": 2});
+        age(&mut epoch, "getEpochInfo", 10);
+        assert_eq!(
+            epoch,
+            json!({"absoluteSlot": 990, "blockHeight": 890, "slotIndex": 0, "epoch": 2})
+        );
+
+        let mut hash = json!({
+            "context": {"slot": 1000},
+            "value": {"blockhash": "abc", "lastValidBlockHeight": 1150}
+        });
+        age(&mut hash, "getLatestBlockhash", 10);
+        assert_eq!(hash["context"]["slot"], 990);
+        assert_eq!(hash["value"]["lastValidBlockHeight"], 1140);
+    }
+
+    #[test]
+    fn unseen_statuses() {
+        let mut seen = HashMap::new();
+        seen.insert("old".to_string(), Instant::now() - Duration::from_secs(10));
+
+        let mut statuses = json!({
+            "context": {"slot": 1},
+            "value": [{"slot": 1, "err": null}, {"slot": 1, "err": null}, null]
+        });
+        hide_unseen(
+            &mut statuses,
+            "getSignatureStatuses",
+            &["old", "new", "missing"],
+            &mut seen,
+            Duration::from_secs(5),
+        );
+        assert!(statuses["value"][0].is_object());
+        assert_eq!(statuses["value"][1], Value::Null);
+        assert!(seen.contains_key("new"));
+        assert!(!seen.contains_key("missing"));
+
+        let mut tx = json!({"slot": 1, "meta": {}});
+        hide_unseen(
+            &mut tx,
+            "getTransaction",
+            &["new"],
+            &mut seen,
+            Duration::ZERO,
+        );
+        assert!(tx.is_object());
+    }
+}